@@ -0,0 +1,362 @@
+//! The `Rc<RefCell<Node>>` search tree the arena replaced, kept as the baseline of the benchmark.
+//!
+//! Ported from the former `mcts::tree` and `utils::rc_wrapper` modules with their UCT selection,
+//! random expansion, random playouts and backpropagation to every parent of a transposition.
+// Nodes are hashed by their board, which never changes once they are created
+#![allow(clippy::mutable_key_type)]
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+use std::time::Instant;
+
+const CUTOFF_MOVES: usize = 200;
+
+struct HashableRcRefCell<T>(Rc<RefCell<T>>);
+struct WeakHashableRcRefCell<T>(Weak<RefCell<T>>);
+
+type NodeRef = HashableRcRefCell<Node>;
+type WNodeRef = WeakHashableRcRefCell<Node>;
+
+impl<T> HashableRcRefCell<T> {
+    fn new(t: T) -> Self {
+        HashableRcRefCell(Rc::new(RefCell::new(t)))
+    }
+
+    fn downgrade(&self) -> WeakHashableRcRefCell<T> {
+        WeakHashableRcRefCell(Rc::downgrade(&self.0))
+    }
+}
+
+impl<T> Clone for HashableRcRefCell<T> {
+    fn clone(&self) -> Self {
+        HashableRcRefCell(Rc::clone(&self.0))
+    }
+}
+
+impl<T: PartialEq> PartialEq for HashableRcRefCell<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // Unsafe avoids double borrow of same element
+        unsafe { *self.0.as_ptr() == *other.0.as_ptr() }
+    }
+}
+
+impl<T: Eq> Eq for HashableRcRefCell<T> {}
+
+impl<T: Hash> Hash for HashableRcRefCell<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        unsafe {
+            (*(*self.0).as_ptr()).hash(state);
+        }
+    }
+}
+
+impl<T> Deref for HashableRcRefCell<T> {
+    type Target = RefCell<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> WeakHashableRcRefCell<T> {
+    fn upgrade(&self) -> Option<HashableRcRefCell<T>> {
+        Weak::upgrade(&self.0).map(HashableRcRefCell)
+    }
+}
+
+pub struct LegacyTree {
+    nodes: HashSet<NodeRef>,
+    head: NodeRef,
+    max_search_depth: usize,
+    max_search_time: f32,
+    rng: rand::rngs::ThreadRng,
+    last_iterations: usize,
+}
+
+impl LegacyTree {
+    pub fn new(initial_board: chess::Board, max_search_depth: usize) -> LegacyTree {
+        let head = HashableRcRefCell::new(Node::new(None, initial_board));
+        let mut nodes = HashSet::<NodeRef>::new();
+        nodes.insert(head.clone());
+        LegacyTree {
+            nodes,
+            head,
+            max_search_depth,
+            max_search_time: 5_f32,
+            rng: rand::thread_rng(),
+            last_iterations: 0,
+        }
+    }
+
+    pub fn set_max_search_time(&mut self, max_search_time: f32) {
+        self.max_search_time = max_search_time;
+    }
+
+    /// Iterations of the last search.
+    pub fn last_iterations(&self) -> usize {
+        self.last_iterations
+    }
+
+    pub fn yield_best_move(&mut self, color_to_play: chess::Color) -> chess::ChessMove {
+        self.last_iterations = self.populate_tree();
+        // Compute argmax amongst children win ratios
+        let best_child_index = (*self.head)
+            .borrow()
+            .children
+            .iter()
+            .map(|x| (**x).borrow().get_win_ratio(Some(color_to_play)))
+            .enumerate()
+            .fold((0_usize, 0_f32), |(id_max, score_max), (i, score)| {
+                if score > score_max {
+                    (i, score)
+                } else {
+                    (id_max, score_max)
+                }
+            })
+            .0;
+        let best_child = (*self.head).borrow().children[best_child_index].clone();
+        let chess_move = Node::get_move_diff(self.head.clone(), best_child);
+        self.prune_tree_based_on_move_and_update_head(chess_move);
+        chess_move
+    }
+
+    fn prune_tree_based_on_move_and_update_head(&mut self, chess_move: chess::ChessMove) {
+        let board = (*self.head).borrow().board;
+        let best_child = HashableRcRefCell::new(Node::new(
+            Some(self.head.clone()),
+            board.make_move_new(chess_move),
+        ));
+        // Drop all but the chosen child nodes
+        (*self.head)
+            .borrow()
+            .children
+            .iter()
+            .filter(|x| **x != best_child)
+            .for_each(|x| Self::drop_node(&mut self.nodes, x));
+        self.nodes.remove(&self.head);
+        self.head = best_child;
+        (*self.head).borrow_mut().parents.clear();
+    }
+
+    fn populate_tree(&mut self) -> usize {
+        let now = Instant::now();
+        let starting_node = self.head.clone();
+        let mut n_iterations: usize = 0;
+        loop {
+            let selected_node = Self::select(starting_node.clone());
+            let expanded_node = self.expand(&selected_node);
+            let result = self.simulate(&expanded_node);
+            Self::backpropagate(&expanded_node.downgrade(), result);
+            n_iterations += 1;
+            if now.elapsed().as_secs_f32() > self.max_search_time {
+                break;
+            }
+        }
+        n_iterations
+    }
+
+    fn select(root: NodeRef) -> NodeRef {
+        if !(*root).borrow().has_children() {
+            return root;
+        }
+        let selected_child_id = (*root)
+            .borrow()
+            .children
+            .iter()
+            .map(|child_node| (**child_node).borrow().score)
+            .enumerate()
+            .fold((0_usize, 0_f32), |(id_max, score_max), (i, score)| {
+                if score > score_max {
+                    (i, score)
+                } else {
+                    (id_max, score_max)
+                }
+            })
+            .0;
+        let selected_child = (*root).borrow().children[selected_child_id].clone();
+        Self::select(selected_child)
+    }
+
+    fn expand(&mut self, root: &NodeRef) -> NodeRef {
+        if (**root).borrow().depth < self.max_search_depth + (*self.head).borrow().depth {
+            let current_board = (**root).borrow().board;
+            let boards =
+                chess::MoveGen::new_legal(&current_board).map(|x| current_board.make_move_new(x));
+            self.add_children(root, boards);
+        }
+        let random_child = (**root).borrow().children.choose(&mut self.rng).cloned();
+        // Target depth reached or terminal board state reached
+        random_child.unwrap_or_else(|| root.clone())
+    }
+
+    fn simulate(&mut self, root: &NodeRef) -> (chess::BoardStatus, chess::Color) {
+        let mut board = (**root).borrow().board;
+        let mut target_board = chess::Board::default();
+        for _ in 0..CUTOFF_MOVES {
+            let mut move_generator = chess::MoveGen::new_legal(&board);
+            let n_moves = move_generator.len();
+            if n_moves == 0 {
+                break;
+            }
+            let move_index = self.rng.gen_range(0, n_moves);
+            board.make_move(move_generator.nth(move_index).unwrap(), &mut target_board);
+            std::mem::swap(&mut board, &mut target_board);
+        }
+        (board.status(), board.side_to_move())
+    }
+
+    fn backpropagate(leaf: &WNodeRef, status: (chess::BoardStatus, chess::Color)) {
+        let Some(leaf) = leaf.upgrade() else {
+            return;
+        };
+        // Stop backpropagation if node already borrowed in DAG
+        if let Ok(mut leaf_node) = leaf.try_borrow_mut() {
+            leaf_node.white_wins += match status {
+                (chess::BoardStatus::Checkmate, chess::Color::White) => 1_f32,
+                (chess::BoardStatus::Checkmate, chess::Color::Black) => 0_f32,
+                _ => 0.5_f32,
+            };
+            leaf_node.visits += 1;
+            leaf_node.score = leaf_node.get_score();
+            // Backpropagate to each parent
+            for parent in &leaf_node.parents {
+                Self::backpropagate(parent, status);
+            }
+        };
+    }
+
+    fn add_children(&mut self, parent: &NodeRef, boards: impl Iterator<Item = chess::Board>) {
+        let child_nodes = boards
+            .map(|x| {
+                let cand_node = NodeRef::new(Node::new(Some(parent.clone()), x));
+                // Transposed nodes get the parent appended to their parents
+                match self.nodes.get(&cand_node) {
+                    Some(existing_cand_node) => {
+                        (**existing_cand_node)
+                            .borrow_mut()
+                            .parents
+                            .push(parent.downgrade());
+                        existing_cand_node.clone()
+                    }
+                    None => {
+                        self.nodes.insert(cand_node.clone());
+                        cand_node
+                    }
+                }
+            })
+            .collect::<Vec<NodeRef>>();
+        (**parent).borrow_mut().children = child_nodes;
+    }
+
+    fn drop_node(nodes: &mut HashSet<NodeRef>, root: &NodeRef) {
+        nodes.remove(root);
+        // Start recursive drop call on child nodes only reachable through root
+        if let Ok(root_ref) = (*root).try_borrow() {
+            root_ref.children.iter().for_each(|x| {
+                // Clean parent refs of root and already dropped parents
+                if let Ok(mut child_ref) = (*x).try_borrow_mut() {
+                    child_ref.parents.retain(|y| match y.upgrade() {
+                        Some(parent) => *parent != **root,
+                        None => false,
+                    });
+                }
+                if (**x).borrow().parents.is_empty() {
+                    Self::drop_node(nodes, x)
+                }
+            });
+        }
+        if let Ok(mut root_ref) = (*root).try_borrow_mut() {
+            root_ref.parents.clear();
+            root_ref.children.clear();
+        }
+    }
+}
+
+struct Node {
+    parents: Vec<WNodeRef>,
+    children: Vec<NodeRef>,
+    board: chess::Board,
+    depth: usize,
+    visits: usize,
+    white_wins: f32,
+    score: f32,
+}
+
+impl Node {
+    fn new(parent: Option<NodeRef>, board: chess::Board) -> Node {
+        let (depth, parents) = match parent {
+            Some(parent) => ((*parent).borrow().depth + 1, vec![parent.downgrade()]),
+            None => (0, Vec::new()),
+        };
+        Node {
+            parents,
+            children: Vec::new(),
+            board,
+            depth,
+            visits: 0,
+            white_wins: 0.,
+            score: f32::INFINITY,
+        }
+    }
+
+    fn has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    /// UCT score, summing the visits of all parents of a transposition.
+    fn get_score(&self) -> f32 {
+        let parent_visits: usize = self
+            .parents
+            .iter()
+            .filter_map(|x| x.upgrade())
+            // Unsafe bc node mutably borrowed in backpropagate
+            .map(|x| unsafe { (*x.as_ptr()).visits })
+            .sum();
+        let win_score = self.get_win_ratio(None);
+        win_score
+            + std::f32::consts::SQRT_2
+                * ((parent_visits.max(1) as f32).ln() / self.visits as f32).sqrt()
+    }
+
+    fn get_win_ratio(&self, color_to_play: Option<chess::Color>) -> f32 {
+        debug_assert!(
+            self.board.side_to_move() == color_to_play.unwrap_or(self.board.side_to_move())
+        );
+        if self.board.side_to_move() == chess::Color::White {
+            self.white_wins / (self.visits as f32)
+        } else {
+            1_f32 - (self.white_wins / self.visits as f32)
+        }
+    }
+
+    fn get_move_diff(parent_node: NodeRef, child_node: NodeRef) -> chess::ChessMove {
+        let child_board = (*child_node).borrow().board;
+        let move_id = (*parent_node)
+            .borrow()
+            .children
+            .iter()
+            .position(|x| (**x).borrow().board == child_board)
+            .expect("Child node not found amongst its parent's children");
+        chess::MoveGen::new_legal(&(*parent_node).borrow().board)
+            .nth(move_id)
+            .unwrap()
+    }
+}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, hasher_state: &mut H) {
+        self.board.get_hash().hash(hasher_state);
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.board.get_hash() == other.board.get_hash()
+    }
+}
+
+impl Eq for Node {}
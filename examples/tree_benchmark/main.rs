@@ -0,0 +1,78 @@
+//! Measures MCTS iterations per second of `Tree::yield_best_move` on a few positions, and the
+//! share of the visits carried over to the position after the chosen move.
+//!
+//! The arena is first compared to the former `Rc<RefCell<Node>>` tree on one thread, then
+//! measured with the given threads and parallel mode.
+//!
+//! Run with `cargo run --release --example tree_benchmark [seconds_per_position] [n_threads] [tree|root|leaf]`.
+mod legacy_tree;
+
+use brocciu::mcts::search::MAX_SEARCH_DEPTH;
+use brocciu::mcts::tree::{ParallelMode, Tree};
+use legacy_tree::LegacyTree;
+use std::str::FromStr;
+
+const POSITIONS: [(&str, &str); 3] = [
+    (
+        "Opening",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    ),
+    (
+        "Middlegame",
+        "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    ),
+    ("Endgame", "8/5pk1/6p1/8/3R4/6P1/5PK1/r7 w - - 0 40"),
+];
+
+fn main() {
+    let search_time = std::env::args()
+        .nth(1)
+        .map(|x| f32::from_str(&x).expect("Search time must be a number of seconds"))
+        .unwrap_or(5_f32);
+    let n_threads = std::env::args()
+        .nth(2)
+        .map(|x| usize::from_str(&x).expect("Thread count must be a positive integer"))
+        .unwrap_or(1);
+    let parallel_mode = match std::env::args().nth(3).as_deref() {
+        None | Some("tree") => ParallelMode::Tree,
+        Some("root") => ParallelMode::Root,
+        Some("leaf") => ParallelMode::Leaf,
+        Some(mode) => panic!("Unknown parallel mode {mode}"),
+    };
+    println!("Rc<RefCell<Node>> tree versus arena, one thread");
+    for (name, fen) in POSITIONS {
+        let board = chess::Board::from_str(fen).expect("Invalid benchmark fen");
+        let mut legacy_tree = LegacyTree::new(board, MAX_SEARCH_DEPTH);
+        legacy_tree.set_max_search_time(search_time);
+        legacy_tree.yield_best_move(board.side_to_move());
+        let legacy_iterations = legacy_tree.last_iterations() as f32 / search_time;
+        let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(search_time);
+        tree.yield_best_move(board.side_to_move());
+        let info = tree
+            .last_search_info()
+            .expect("The search reports its statistics");
+        println!(
+            "{name:<12} {legacy_iterations:>9.0} iterations/s => {:>9.0} iterations/s",
+            info.iterations as f32 / info.elapsed
+        );
+    }
+    println!("Arena, {n_threads} thread(s)");
+    for (name, fen) in POSITIONS {
+        let board = chess::Board::from_str(fen).expect("Invalid benchmark fen");
+        let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(search_time);
+        tree.set_n_threads(n_threads);
+        tree.set_parallel_mode(parallel_mode);
+        tree.yield_best_move(board.side_to_move());
+        let info = tree
+            .last_search_info()
+            .expect("The search reports its statistics");
+        let (n_iterations, elapsed) = (info.iterations, info.elapsed);
+        println!(
+            "{name:<12} {n_iterations:>9} iterations in {elapsed:.2}s => {:.0} iterations/s, {:.1}% carried over",
+            n_iterations as f32 / elapsed,
+            100_f32 * tree.carried_visits() as f32 / n_iterations.max(1) as f32
+        );
+    }
+}
@@ -1,71 +1,154 @@
+use crate::config::BotConfig;
+use crate::matchmaking::Matchmaker;
+use crate::mcts::config::SearchConfig;
+use crate::mcts::search::Engine;
 use crate::utils;
 use crate::utils::threadpool::ThreadPool;
-use lichess_api::model::board::stream::events::Event;
+use lichess_api::error::Error as LichessError;
+use lichess_api::model::board::stream::events::{Event, GameEventInfo};
 use reqwest::Client;
-use std::collections::HashMap;
-use std::error::Error;
-use std::pin::Pin;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
+use std::time::Duration;
 use tokio::task::JoinHandle as tJoinHandle;
 
-pub struct ApiHandler {
+const TCP_KEEPALIVE_SECS: u64 = 30;
+const LICHESS_URL: &str = "https://lichess.org";
+
+pub struct ApiHandler<E: Engine<E>> {
     pub lichess_api: lichess_api::client::LichessApi<reqwest::Client>,
-    pub queues: Mutex<Queues>,
-    pub game_handles: Mutex<HashMap<String, Arc<Mutex<crate::game::BotGame>>>>,
+    pub game_handles: Mutex<HashMap<String, Arc<Mutex<crate::game::BotGame<E>>>>>,
+    // Supervising task of each game being played
+    pub game_tasks: Mutex<HashMap<String, tJoinHandle<()>>>,
+    // Opponent of each game being played, by game id
+    pub game_opponents: Mutex<HashMap<String, String>>,
+    // Games started while `max_concurrent_games` were running, played once a game ends
+    pub waiting_games: Mutex<VecDeque<GameEventInfo>>,
+    pub matchmaker: Mutex<Matchmaker>,
     pub pool: utils::threadpool::ThreadPool,
     pub user: String,
+    // Authenticates the requests to the endpoints lichess-api does not wrap
+    token: String,
+    pub search_config: SearchConfig,
+    pub bot_config: BotConfig,
+    // Set once a shutdown signal was received, no new game is started then
+    shutting_down: AtomicBool,
+    // Ends the searches of all games, raised before the pool joins its workers
+    pub stop_flag: Arc<AtomicBool>,
 }
 
-pub struct Queues {
-    pub wait_queue: Vec<Event>,
-    pub processing_queue: Vec<Event>,
-}
-
-impl Queues {
-    pub fn new() -> Mutex<Queues> {
-        Mutex::new(Queues {
-            wait_queue: Vec::<Event>::new(),
-            processing_queue: Vec::<Event>::new(),
-        })
-    }
-}
-
-impl ApiHandler {
-    pub fn new() -> Result<ApiHandler, Box<dyn std::error::Error>> {
+impl<E: Engine<E>> ApiHandler<E> {
+    pub fn new() -> Result<ApiHandler<E>, Box<dyn std::error::Error>> {
+        // Load and validate the search parameters
+        let search_config = utils::parse_args::get_search_config()?;
+        let bot_config = utils::parse_args::get_bot_config()?;
         // Load API Information from JSON file
-        let api_info = utils::parse_args::get_api_tokens().unwrap();
-        // // Build API client
-        let client: Client = reqwest::ClientBuilder::new().build().unwrap();
-        let lichess_api = lichess_api::client::LichessApi::new(client, Some(api_info.token));
-        let queues = Queues::new();
-        // let thread_handles = HashMap::<String, Mutex<tJoinHandle<()>>>::new();
-        let game_handles = Mutex::new(HashMap::<String, Arc<Mutex<crate::game::BotGame>>>::new());
-        let pool = ThreadPool::new(2);
+        let api_info = utils::parse_args::get_api_tokens()?;
+        // Keepalive probes detect dropped connections of the long-lived event streams
+        let client: Client = reqwest::ClientBuilder::new()
+            .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE_SECS))
+            .build()?;
+        let lichess_api =
+            lichess_api::client::LichessApi::new(client, Some(api_info.token.clone()));
+        let game_handles =
+            Mutex::new(HashMap::<String, Arc<Mutex<crate::game::BotGame<E>>>>::new());
+        let game_tasks = Mutex::new(HashMap::<String, tJoinHandle<()>>::new());
+        // One search thread per game, games never wait on each other's search
+        let pool = ThreadPool::new(bot_config.max_concurrent_games);
         let api = ApiHandler {
             lichess_api,
-            queues,
             game_handles,
+            game_tasks,
+            game_opponents: Mutex::new(HashMap::new()),
+            waiting_games: Mutex::new(VecDeque::new()),
+            matchmaker: Mutex::new(Matchmaker::default()),
             pool,
             user: api_info.user,
+            token: api_info.token,
+            search_config,
+            bot_config,
+            shutting_down: AtomicBool::new(false),
+            stop_flag: Arc::new(AtomicBool::new(false)),
         };
         Ok(api)
     }
 
+    /// Number of games whose task is still running.
+    pub fn running_games(&self) -> usize {
+        self.game_tasks
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|x| !x.is_finished())
+            .count()
+    }
+
+    /// Number of games running or waiting for a running game to end.
+    pub fn active_games(&self) -> usize {
+        self.running_games() + self.waiting_games.lock().unwrap().len()
+    }
+
+    /// Usernames of the opponents of the games running or waiting for a running game to end.
+    pub fn opponents(&self) -> Vec<String> {
+        let mut opponents = self
+            .game_opponents
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<String>>();
+        let waiting_games = self.waiting_games.lock().unwrap();
+        opponents.extend(waiting_games.iter().map(|x| x.opponent.username.clone()));
+        opponents
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    pub fn start_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    /// Ends the running searches, their moves are not played anymore.
+    pub fn stop_searches(&self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Posts to the bot endpoint `/api/bot/{path}`, for the bot endpoints lichess-api has no
+    /// request for. Returns whether Lichess acknowledged the request.
+    pub async fn bot_post(&self, path: &str) -> Result<bool, LichessError> {
+        let request_error = |x: reqwest::Error| LichessError::Request(x.to_string());
+        let response = self
+            .lichess_api
+            .client
+            .post(format!("{LICHESS_URL}/api/bot/{path}"))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status())
+            .map_err(request_error)?;
+        let body = response.text().await.map_err(request_error)?;
+        let answer: lichess_api::model::Ok = serde_json::from_str(&body)?;
+        Ok(answer.ok)
+    }
+
     pub async fn get_event_stream(
         &self,
     ) -> Result<
         impl tokio_stream::StreamExt<Item = Result<Event, lichess_api::error::Error>>,
         lichess_api::error::Error,
     > {
-        let mut game_ids = Vec::<String>::new();
         // Stream incoming events
         let request = lichess_api::model::board::stream::events::GetRequest::new();
-        let mut event_stream = self.lichess_api.bot_stream_incoming_events(request).await;
-        event_stream
+        self.lichess_api.bot_stream_incoming_events(request).await
     }
-
 }
 
-unsafe impl Send for ApiHandler {}
-unsafe impl Sync for ApiHandler {}
+impl<E: Engine<E>> Drop for ApiHandler<E> {
+    /// Stops the searches before `pool` is dropped, whose workers are joined once their job ends.
+    fn drop(&mut self) {
+        self.stop_searches();
+    }
+}
@@ -0,0 +1,7 @@
+fn main() {
+    // Run the MCTS engine behind the Universal Chess Interface
+    if let Err(error) = brocciu::uci::run() {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
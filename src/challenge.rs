@@ -0,0 +1,366 @@
+use crate::utils::misc::board_from_fen;
+use lichess_api::model::challenges::decline::Reason;
+use lichess_api::model::challenges::{ChallengeJson, TimeControl};
+use lichess_api::model::{Title, VariantKey};
+use serde::{Deserialize, Serialize};
+
+// Variants `chess::Board` is able to play, it has no Chess960 castling
+const SUPPORTED_VARIANTS: [VariantKey; 2] = [VariantKey::Standard, VariantKey::FromPosition];
+
+/// Rules an incoming challenge has to satisfy to be accepted.
+///
+/// Clock times are in seconds, usernames are compared case-insensitively.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChallengePolicy {
+    pub variants: Vec<VariantKey>,
+    pub min_initial_time: u32,
+    pub max_initial_time: u32,
+    pub min_increment: u32,
+    pub max_increment: u32,
+    pub allow_correspondence: bool,
+    pub allow_unlimited: bool,
+    pub allow_rated: bool,
+    pub allow_casual: bool,
+    pub allow_bots: bool,
+    pub allow_humans: bool,
+    /// Largest difference between the challenger's rating and the bot's one, `None` for any
+    pub max_rating_difference: Option<u32>,
+    /// Only these users may challenge the bot, everyone if empty
+    pub allow_list: Vec<String>,
+    pub deny_list: Vec<String>,
+}
+
+impl Default for ChallengePolicy {
+    fn default() -> ChallengePolicy {
+        ChallengePolicy {
+            variants: vec![VariantKey::Standard, VariantKey::FromPosition],
+            min_initial_time: 0,
+            max_initial_time: 10_800,
+            min_increment: 0,
+            max_increment: 180,
+            allow_correspondence: true,
+            allow_unlimited: true,
+            allow_rated: true,
+            allow_casual: true,
+            allow_bots: true,
+            allow_humans: true,
+            max_rating_difference: None,
+            allow_list: Vec::new(),
+            deny_list: Vec::new(),
+        }
+    }
+}
+
+impl ChallengePolicy {
+    /// Reason to decline `challenge` with, `None` if it is acceptable.
+    ///
+    /// Permanent reasons come first, so that the challenger is only told to come back later
+    /// when the challenge would be accepted otherwise.
+    pub fn decline_reason(
+        &self,
+        challenge: &ChallengeJson,
+        running_games: usize,
+        max_concurrent_games: usize,
+    ) -> Option<Reason> {
+        let initial_fen = challenge.initial_fen.as_deref();
+        let challenge = &challenge.base;
+        let challenger = &challenge.challenger.user.name;
+        let is_listed =
+            |list: &Vec<String>| list.iter().any(|x| x.eq_ignore_ascii_case(challenger));
+        if is_listed(&self.deny_list)
+            || !(self.allow_list.is_empty() || is_listed(&self.allow_list))
+        {
+            return Some(Reason::Generic);
+        }
+        if !self.variants.contains(&challenge.variant.key) {
+            return Some(Reason::Variant);
+        }
+        // Positions the board can not read are only played in the standard variant
+        if initial_fen.is_some_and(|x| x != "startpos" && board_from_fen(x).is_none()) {
+            return Some(Reason::Standard);
+        }
+        if let Some(reason) = self.time_control_reason(&challenge.time_control) {
+            return Some(reason);
+        }
+        match challenge.rated {
+            true if !self.allow_rated => return Some(Reason::Casual),
+            false if !self.allow_casual => return Some(Reason::Rated),
+            _ => {}
+        }
+        match challenge.challenger.user.title {
+            Some(Title::Bot) if !self.allow_bots => return Some(Reason::NoBot),
+            Some(Title::Bot) => {}
+            _ if !self.allow_humans => return Some(Reason::OnlyBot),
+            _ => {}
+        }
+        // The rating of the bot in the challenge's perf is the one of the challenged user
+        if let (Some(max_difference), Some(bot)) =
+            (self.max_rating_difference, &challenge.dest_user)
+        {
+            if challenge.challenger.rating.abs_diff(bot.rating) > max_difference {
+                return Some(Reason::Generic);
+            }
+        }
+        if running_games >= max_concurrent_games {
+            return Some(Reason::Later);
+        }
+        None
+    }
+
+    fn time_control_reason(&self, time_control: &TimeControl) -> Option<Reason> {
+        match *time_control {
+            TimeControl::Clock {
+                limit, increment, ..
+            } => {
+                if limit < self.min_initial_time || increment < self.min_increment {
+                    Some(Reason::TooFast)
+                } else if limit > self.max_initial_time || increment > self.max_increment {
+                    Some(Reason::TooSlow)
+                } else {
+                    None
+                }
+            }
+            TimeControl::Correspondance { .. } if !self.allow_correspondence => {
+                Some(Reason::TimeControl)
+            }
+            TimeControl::Unlimited if !self.allow_unlimited => Some(Reason::TimeControl),
+            _ => None,
+        }
+    }
+
+    /// Description of the first invalid rule, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            (
+                !self.variants.is_empty(),
+                "variants must not be empty".to_string(),
+            ),
+            (
+                self.variants.iter().all(|x| SUPPORTED_VARIANTS.contains(x)),
+                format!(
+                    "variants must be among {}",
+                    serde_json::to_string(&SUPPORTED_VARIANTS).unwrap_or_default()
+                ),
+            ),
+            (
+                self.min_initial_time <= self.max_initial_time,
+                "min_initial_time must not exceed max_initial_time".to_string(),
+            ),
+            (
+                self.min_increment <= self.max_increment,
+                "min_increment must not exceed max_increment".to_string(),
+            ),
+            (
+                self.allow_rated || self.allow_casual,
+                "allow_rated and allow_casual must not both be false".to_string(),
+            ),
+            (
+                self.allow_bots || self.allow_humans,
+                "allow_bots and allow_humans must not both be false".to_string(),
+            ),
+        ];
+        match checks.into_iter().find(|(is_valid, _)| !is_valid) {
+            Some((_, message)) => Err(message),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Rated 3+2 blitz challenge from the human `Challenger`, rated 1500, to a bot rated 1600.
+    fn blitz_challenge() -> ChallengeJson {
+        serde_json::from_value(json!({
+            "id": "H9fIRZUk",
+            "url": "https://lichess.org/H9fIRZUk",
+            "color": "random",
+            "status": "created",
+            "challenger": {"id": "challenger", "name": "Challenger", "rating": 1500},
+            "destUser": {"id": "bot", "name": "Bot", "title": "BOT", "rating": 1600},
+            "variant": {"key": "standard", "name": "Standard", "short": "Std"},
+            "rated": true,
+            "speed": "blitz",
+            "timeControl": {"type": "clock", "limit": 180, "increment": 2, "show": "3+2"},
+            "perf": {"icon": "", "name": "Blitz"}
+        }))
+        .unwrap()
+    }
+
+    fn decline_reason(policy: &ChallengePolicy, challenge: &ChallengeJson) -> Option<Reason> {
+        policy.decline_reason(challenge, 0, 1)
+    }
+
+    #[test]
+    fn accepts_challenges_within_the_rules() {
+        assert_eq!(
+            decline_reason(&ChallengePolicy::default(), &blitz_challenge()),
+            None
+        );
+    }
+
+    #[test]
+    fn declines_unsupported_variants() {
+        let policy = ChallengePolicy::default();
+        let mut challenge = blitz_challenge();
+        challenge.base.variant.key = VariantKey::Chess960;
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::Variant));
+        let policy = ChallengePolicy {
+            variants: vec![VariantKey::FromPosition],
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(
+            decline_reason(&policy, &blitz_challenge()),
+            Some(Reason::Variant)
+        );
+    }
+
+    #[test]
+    fn declines_unreadable_positions() {
+        let mut challenge = blitz_challenge();
+        challenge.base.variant.key = VariantKey::FromPosition;
+        challenge.initial_fen = Some("8/8/8 w - - 0 1".to_string());
+        let policy = ChallengePolicy::default();
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::Standard));
+        challenge.initial_fen = Some("startpos".to_string());
+        assert_eq!(decline_reason(&policy, &challenge), None);
+    }
+
+    #[test]
+    fn declines_clocks_out_of_range() {
+        let policy = ChallengePolicy {
+            min_initial_time: 300,
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(
+            decline_reason(&policy, &blitz_challenge()),
+            Some(Reason::TooFast)
+        );
+        let policy = ChallengePolicy {
+            max_increment: 1,
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(
+            decline_reason(&policy, &blitz_challenge()),
+            Some(Reason::TooSlow)
+        );
+    }
+
+    #[test]
+    fn declines_disabled_time_controls() {
+        let policy = ChallengePolicy {
+            allow_correspondence: false,
+            allow_unlimited: false,
+            ..ChallengePolicy::default()
+        };
+        let mut challenge = blitz_challenge();
+        challenge.base.time_control = TimeControl::Correspondance { days_per_turn: 3 };
+        assert_eq!(
+            decline_reason(&policy, &challenge),
+            Some(Reason::TimeControl)
+        );
+        challenge.base.time_control = TimeControl::Unlimited;
+        assert_eq!(
+            decline_reason(&policy, &challenge),
+            Some(Reason::TimeControl)
+        );
+    }
+
+    #[test]
+    fn declines_by_rated_mode() {
+        let policy = ChallengePolicy {
+            allow_rated: false,
+            ..ChallengePolicy::default()
+        };
+        let mut challenge = blitz_challenge();
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::Casual));
+        challenge.base.rated = false;
+        assert_eq!(decline_reason(&policy, &challenge), None);
+        let policy = ChallengePolicy {
+            allow_casual: false,
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::Rated));
+    }
+
+    #[test]
+    fn declines_by_title() {
+        let policy = ChallengePolicy {
+            allow_humans: false,
+            ..ChallengePolicy::default()
+        };
+        let mut challenge = blitz_challenge();
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::OnlyBot));
+        // Titled players are humans too
+        challenge.base.challenger.user.title = Some(Title::Gm);
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::OnlyBot));
+        challenge.base.challenger.user.title = Some(Title::Bot);
+        assert_eq!(decline_reason(&policy, &challenge), None);
+        let policy = ChallengePolicy {
+            allow_bots: false,
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(decline_reason(&policy, &challenge), Some(Reason::NoBot));
+    }
+
+    #[test]
+    fn declines_by_username() {
+        let policy = ChallengePolicy {
+            deny_list: vec!["CHALLENGER".to_string()],
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(
+            decline_reason(&policy, &blitz_challenge()),
+            Some(Reason::Generic)
+        );
+        let policy = ChallengePolicy {
+            allow_list: vec!["friend".to_string()],
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(
+            decline_reason(&policy, &blitz_challenge()),
+            Some(Reason::Generic)
+        );
+        let policy = ChallengePolicy {
+            allow_list: vec!["friend".to_string(), "challenger".to_string()],
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(decline_reason(&policy, &blitz_challenge()), None);
+    }
+
+    #[test]
+    fn declines_distant_ratings() {
+        let policy = ChallengePolicy {
+            max_rating_difference: Some(50),
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(
+            decline_reason(&policy, &blitz_challenge()),
+            Some(Reason::Generic)
+        );
+        let policy = ChallengePolicy {
+            max_rating_difference: Some(100),
+            ..ChallengePolicy::default()
+        };
+        assert_eq!(decline_reason(&policy, &blitz_challenge()), None);
+    }
+
+    #[test]
+    fn asks_to_come_back_later_only_when_busy() {
+        let policy = ChallengePolicy::default();
+        assert_eq!(
+            policy.decline_reason(&blitz_challenge(), 2, 2),
+            Some(Reason::Later)
+        );
+        // Permanent reasons take precedence
+        let mut challenge = blitz_challenge();
+        challenge.base.variant.key = VariantKey::Atomic;
+        assert_eq!(
+            policy.decline_reason(&challenge, 2, 2),
+            Some(Reason::Variant)
+        );
+    }
+}
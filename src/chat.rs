@@ -0,0 +1,368 @@
+use crate::mcts::tree::SearchInfo;
+use lichess_api::model::Room;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Longest message Lichess accepts in a game chat
+const MAX_MESSAGE_LENGTH: usize = 140;
+
+/// Messages of the bot in the game chats.
+///
+/// Templates may use `{bot}`, `{opponent}` and `{version}`, command replies `{eval}`, `{pv}`,
+/// `{nodes}` and `{commands}` as well. An empty template sends nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    pub enabled: bool,
+    /// Sent to the player room when a game starts
+    pub greeting: String,
+    /// Sent to the spectator room when a game starts
+    pub spectator_greeting: String,
+    /// Sent to both rooms once a game is over, aborted games excepted
+    pub goodbye: String,
+    /// Rooms in which commands are answered
+    pub player_commands: bool,
+    pub spectator_commands: bool,
+    /// Minimal time between two command replies in a room in seconds, commands sent in between
+    /// are ignored
+    pub command_cooldown: u64,
+    pub templates: CommandTemplates,
+}
+
+impl Default for ChatConfig {
+    fn default() -> ChatConfig {
+        ChatConfig {
+            enabled: true,
+            greeting: "Hi {opponent}, I am {bot}. Type !help for my commands. Good luck!"
+                .to_string(),
+            spectator_greeting: "Hi all! Type !help for my commands.".to_string(),
+            goodbye: "Good game, thanks for playing!".to_string(),
+            player_commands: true,
+            spectator_commands: true,
+            command_cooldown: 5,
+            templates: CommandTemplates::default(),
+        }
+    }
+}
+
+/// Replies to the chat commands.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandTemplates {
+    pub eval: String,
+    pub pv: String,
+    pub nodes: String,
+    pub name: String,
+    pub help: String,
+    /// Reply to `!eval`, `!pv` and `!nodes` before the bot's first search
+    pub no_search: String,
+}
+
+impl Default for CommandTemplates {
+    fn default() -> CommandTemplates {
+        CommandTemplates {
+            eval: "My winning chances: {eval}".to_string(),
+            pv: "Expected line: {pv}".to_string(),
+            nodes: "Last search: {nodes} iterations".to_string(),
+            name: "{bot} {version}, a Monte Carlo tree search engine".to_string(),
+            help: "Commands: {commands}".to_string(),
+            no_search: "I have not searched a move yet".to_string(),
+        }
+    }
+}
+
+/// Commands understood in the game chats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatCommand {
+    Eval,
+    Pv,
+    Nodes,
+    Help,
+    Name,
+}
+
+impl ChatCommand {
+    const ALL: [(&'static str, ChatCommand); 5] = [
+        ("!eval", ChatCommand::Eval),
+        ("!pv", ChatCommand::Pv),
+        ("!nodes", ChatCommand::Nodes),
+        ("!help", ChatCommand::Help),
+        ("!name", ChatCommand::Name),
+    ];
+
+    /// Command starting a chat message, if any.
+    pub fn parse(text: &str) -> Option<ChatCommand> {
+        let word = text.split_whitespace().next()?;
+        ChatCommand::ALL
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(word))
+            .map(|(_, command)| *command)
+    }
+}
+
+/// Chat state of a game, kept across reconnections of its stream.
+#[derive(Debug, Default)]
+pub struct GameChat {
+    opponent: String,
+    greeted: bool,
+    // Times of the last command replies in the player and spectator rooms
+    player_reply: Option<Instant>,
+    spectator_reply: Option<Instant>,
+}
+
+impl GameChat {
+    /// Messages opening a game which just started, nothing if the bot greeted already or
+    /// joins a game under way.
+    pub fn greetings(
+        &mut self,
+        config: &ChatConfig,
+        bot: &str,
+        opponent: &str,
+        n_moves: usize,
+    ) -> Vec<(Room, String)> {
+        self.opponent = opponent.to_string();
+        if !config.enabled || self.greeted || n_moves >= 2 {
+            return Vec::new();
+        }
+        self.greeted = true;
+        [
+            (Room::Player, &config.greeting),
+            (Room::Spectator, &config.spectator_greeting),
+        ]
+        .into_iter()
+        .filter_map(|(room, template)| Some((room, self.render(template, bot, &[])?)))
+        .collect()
+    }
+
+    /// Messages closing a game with the given Lichess status.
+    pub fn goodbyes(&self, config: &ChatConfig, bot: &str, status: &str) -> Vec<(Room, String)> {
+        if !config.enabled || status == "aborted" {
+            return Vec::new();
+        }
+        match self.render(&config.goodbye, bot, &[]) {
+            Some(message) => vec![(Room::Player, message.clone()), (Room::Spectator, message)],
+            None => Vec::new(),
+        }
+    }
+
+    /// Reply to a chat message sent in `room`, `None` if it is no command or the room is still
+    /// cooling down.
+    ///
+    /// `search_info` is the one of the bot's last search.
+    pub fn reply(
+        &mut self,
+        config: &ChatConfig,
+        bot: &str,
+        text: &str,
+        room: &Room,
+        search_info: Option<&SearchInfo>,
+    ) -> Option<String> {
+        let command = ChatCommand::parse(text)?;
+        let (allowed, last_reply) = match room {
+            Room::Player => (config.player_commands, &mut self.player_reply),
+            Room::Spectator => (config.spectator_commands, &mut self.spectator_reply),
+        };
+        let cooldown = Duration::from_secs(config.command_cooldown);
+        if !config.enabled || !allowed || last_reply.is_some_and(|x| x.elapsed() < cooldown) {
+            return None;
+        }
+        *last_reply = Some(Instant::now());
+        let templates = &config.templates;
+        let template = match (command, search_info) {
+            (ChatCommand::Help, _) => &templates.help,
+            (ChatCommand::Name, _) => &templates.name,
+            (_, None) => &templates.no_search,
+            (ChatCommand::Eval, Some(_)) => &templates.eval,
+            (ChatCommand::Pv, Some(_)) => &templates.pv,
+            (ChatCommand::Nodes, Some(_)) => &templates.nodes,
+        };
+        let mut values = vec![(
+            "commands",
+            ChatCommand::ALL
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<&str>>()
+                .join(" "),
+        )];
+        if let Some(info) = search_info {
+            values.push(("eval", format!("{:.0}%", 100_f32 * info.win_ratio)));
+            values.push((
+                "pv",
+                info.principal_variation
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            ));
+            values.push(("nodes", info.iterations.to_string()));
+        }
+        self.render(template, bot, &values)
+    }
+
+    /// Fills the placeholders of `template`, `None` for an empty template. Messages are cut to
+    /// the length Lichess accepts.
+    fn render(&self, template: &str, bot: &str, values: &[(&str, String)]) -> Option<String> {
+        if template.is_empty() {
+            return None;
+        }
+        let common = [
+            ("bot", bot.to_string()),
+            ("opponent", self.opponent.clone()),
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+        ];
+        let message = common
+            .iter()
+            .chain(values)
+            .fold(template.to_string(), |message, (key, value)| {
+                message.replace(&format!("{{{key}}}"), value)
+            });
+        Some(message.chars().take(MAX_MESSAGE_LENGTH).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn search_info() -> SearchInfo {
+        SearchInfo {
+            iterations: 1234,
+            elapsed: 1_f32,
+            win_ratio: 0.625,
+            principal_variation: vec![
+                chess::ChessMove::from_str("e2e4").unwrap(),
+                chess::ChessMove::from_str("e7e5").unwrap(),
+            ],
+            carried_visits: 0,
+            mate: None,
+        }
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(ChatCommand::parse("!eval"), Some(ChatCommand::Eval));
+        assert_eq!(ChatCommand::parse("  !PV please"), Some(ChatCommand::Pv));
+        assert_eq!(ChatCommand::parse("!nodes"), Some(ChatCommand::Nodes));
+        assert_eq!(ChatCommand::parse("!help"), Some(ChatCommand::Help));
+        assert_eq!(ChatCommand::parse("!name"), Some(ChatCommand::Name));
+        assert_eq!(ChatCommand::parse("what is !eval"), None);
+        assert_eq!(ChatCommand::parse("!evaluation"), None);
+        assert_eq!(ChatCommand::parse(""), None);
+    }
+
+    #[test]
+    fn replies_with_the_last_search() {
+        let config = ChatConfig::default();
+        let mut chat = GameChat::default();
+        let info = search_info();
+        let reply = |chat: &mut GameChat, text, room| {
+            chat.reply(&config, "brocciu", text, &room, Some(&info))
+        };
+        assert_eq!(
+            reply(&mut chat, "!eval", Room::Player).as_deref(),
+            Some("My winning chances: 62%")
+        );
+        assert_eq!(
+            reply(&mut chat, "!pv", Room::Spectator).as_deref(),
+            Some("Expected line: e2e4 e7e5")
+        );
+        let mut chat = GameChat::default();
+        assert_eq!(
+            reply(&mut chat, "!help", Room::Player).as_deref(),
+            Some("Commands: !eval !pv !nodes !help !name")
+        );
+        assert_eq!(
+            GameChat::default()
+                .reply(&config, "brocciu", "!nodes", &Room::Player, None)
+                .as_deref(),
+            Some("I have not searched a move yet")
+        );
+    }
+
+    #[test]
+    fn rooms_cool_down_separately() {
+        let config = ChatConfig::default();
+        let mut chat = GameChat::default();
+        let mut reply = |text, room| chat.reply(&config, "brocciu", text, &room, None);
+        assert!(reply("!name", Room::Player).is_some());
+        assert!(reply("!name", Room::Player).is_none());
+        assert!(reply("!name", Room::Spectator).is_some());
+        // Messages which are no commands are never answered
+        assert!(reply("hello", Room::Spectator).is_none());
+        let config = ChatConfig {
+            command_cooldown: 0,
+            ..ChatConfig::default()
+        };
+        let mut chat = GameChat::default();
+        assert!(chat
+            .reply(&config, "brocciu", "!name", &Room::Player, None)
+            .is_some());
+        assert!(chat
+            .reply(&config, "brocciu", "!name", &Room::Player, None)
+            .is_some());
+    }
+
+    #[test]
+    fn ignores_commands_of_disabled_rooms() {
+        let config = ChatConfig {
+            spectator_commands: false,
+            ..ChatConfig::default()
+        };
+        let mut chat = GameChat::default();
+        assert!(chat
+            .reply(&config, "brocciu", "!help", &Room::Spectator, None)
+            .is_none());
+        assert!(chat
+            .reply(&config, "brocciu", "!help", &Room::Player, None)
+            .is_some());
+    }
+
+    #[test]
+    fn greets_once_at_the_start_of_a_game() {
+        let config = ChatConfig::default();
+        let mut chat = GameChat::default();
+        let greetings = chat.greetings(&config, "brocciu", "rival", 0);
+        assert_eq!(
+            greetings,
+            vec![
+                (
+                    Room::Player,
+                    "Hi rival, I am brocciu. Type !help for my commands. Good luck!".to_string()
+                ),
+                (
+                    Room::Spectator,
+                    "Hi all! Type !help for my commands.".to_string()
+                ),
+            ]
+        );
+        assert!(chat.greetings(&config, "brocciu", "rival", 1).is_empty());
+        // Games joined under way
+        assert!(GameChat::default()
+            .greetings(&config, "brocciu", "rival", 2)
+            .is_empty());
+    }
+
+    #[test]
+    fn says_goodbye_unless_aborted() {
+        let config = ChatConfig::default();
+        let chat = GameChat::default();
+        assert_eq!(chat.goodbyes(&config, "brocciu", "mate").len(), 2);
+        assert!(chat.goodbyes(&config, "brocciu", "aborted").is_empty());
+        let config = ChatConfig {
+            goodbye: String::new(),
+            ..ChatConfig::default()
+        };
+        assert!(chat.goodbyes(&config, "brocciu", "mate").is_empty());
+    }
+
+    #[test]
+    fn cuts_long_messages() {
+        let config = ChatConfig {
+            greeting: "{opponent}".repeat(20),
+            ..ChatConfig::default()
+        };
+        let greetings = GameChat::default().greetings(&config, "brocciu", "rival_name", 0);
+        assert_eq!(greetings[0].1.chars().count(), MAX_MESSAGE_LENGTH);
+    }
+}
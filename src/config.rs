@@ -0,0 +1,149 @@
+use crate::challenge::ChallengePolicy;
+use crate::chat::ChatConfig;
+use crate::matchmaking::MatchmakingConfig;
+use crate::utils::parse_args::apply_override;
+use core::fmt;
+use lichess_api::model::challenges::decline::Reason;
+use lichess_api::model::challenges::ChallengeJson;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Settings of the Lichess bot account, read from `configs/default_bot.json`.
+///
+/// Missing fields take their default value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    /// Games played at the same time, further challenges are declined
+    pub max_concurrent_games: usize,
+    /// Rules of the incoming challenges to accept
+    pub challenge: ChallengePolicy,
+    /// Challenges sent to online bots
+    pub matchmaking: MatchmakingConfig,
+    /// Answers to the opponent's requests during a game
+    pub game: GamePolicy,
+    /// Greetings and command replies in the game chats
+    pub chat: ChatConfig,
+    /// Running games when the bot is asked to stop
+    pub shutdown: ShutdownPolicy,
+}
+
+/// How the bot reacts to its opponent during a game, durations are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamePolicy {
+    pub accept_takebacks: bool,
+    /// Time the opponent has for its first move before the game is aborted
+    pub abort_timeout: u64,
+    /// Claim the victory once an opponent who left the game may be considered gone
+    pub claim_victory: bool,
+}
+
+impl Default for GamePolicy {
+    fn default() -> GamePolicy {
+        GamePolicy {
+            accept_takebacks: false,
+            abort_timeout: 30,
+            claim_victory: true,
+        }
+    }
+}
+
+/// What happens to the running games once the bot receives SIGINT or SIGTERM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownPolicy {
+    /// Play the running games until they end, resign them right away otherwise
+    pub finish_games: bool,
+    /// Time in seconds the running games have to end, the remaining ones are resigned
+    pub drain_timeout: u64,
+}
+
+impl Default for ShutdownPolicy {
+    fn default() -> ShutdownPolicy {
+        ShutdownPolicy {
+            finish_games: true,
+            drain_timeout: 300,
+        }
+    }
+}
+
+impl Default for BotConfig {
+    fn default() -> BotConfig {
+        BotConfig {
+            max_concurrent_games: 2,
+            challenge: ChallengePolicy::default(),
+            matchmaking: MatchmakingConfig::default(),
+            game: GamePolicy::default(),
+            chat: ChatConfig::default(),
+            shutdown: ShutdownPolicy::default(),
+        }
+    }
+}
+
+impl BotConfig {
+    /// Parses and validates a configuration, applying `overrides` (`key=value`, nested keys
+    /// separated by dots) on top of the JSON document.
+    pub fn from_json(json: &str, overrides: &[String]) -> Result<BotConfig, BotConfigError> {
+        let mut value: Value = serde_json::from_str(json)
+            .map_err(|x| BotConfigError::new(format!("malformed json: {x}")))?;
+        for entry in overrides {
+            apply_override(&mut value, entry).map_err(BotConfigError::new)?;
+        }
+        let config: BotConfig =
+            serde_json::from_value(value).map_err(|x| BotConfigError::new(x.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), BotConfigError> {
+        let checks = [
+            (
+                self.max_concurrent_games >= 1,
+                "max_concurrent_games must be at least 1",
+            ),
+            (
+                self.game.abort_timeout >= 1,
+                "game.abort_timeout must be at least 1",
+            ),
+        ];
+        if let Some((_, message)) = checks.iter().find(|(is_valid, _)| !is_valid) {
+            return Err(BotConfigError::new(message.to_string()));
+        }
+        self.challenge
+            .validate()
+            .map_err(|x| BotConfigError::new(format!("challenge.{x}")))?;
+        self.matchmaking
+            .validate()
+            .map_err(|x| BotConfigError::new(format!("matchmaking.{x}")))
+    }
+
+    /// Reason to decline `challenge` with, `None` if it is accepted.
+    pub fn decline_reason(
+        &self,
+        challenge: &ChallengeJson,
+        running_games: usize,
+    ) -> Option<Reason> {
+        self.challenge
+            .decline_reason(challenge, running_games, self.max_concurrent_games)
+    }
+}
+
+#[derive(Debug)]
+pub struct BotConfigError {
+    message: String,
+}
+
+impl BotConfigError {
+    fn new(message: String) -> BotConfigError {
+        BotConfigError { message }
+    }
+}
+
+impl fmt::Display for BotConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid bot configuration: {}", self.message)
+    }
+}
+
+impl std::error::Error for BotConfigError {}
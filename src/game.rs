@@ -1,136 +1,443 @@
 use crate::api::ApiHandler;
-use crate::mcts;
-use crate::mcts::search::NoAvailableMoveError;
-use crate::utils::misc::api_to_chess_color;
-use chess::Color as ChessColor;
-use lichess_api::model::challenges::ChallengeJson;
-use rand::seq::SliceRandom;
-use std::error::Error;
+use crate::utils::misc::{api_to_chess_color, board_from_fen};
 use std::str::FromStr;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 
+use crate::mcts::config::SearchConfig;
 use crate::mcts::search::Engine;
+use crate::mcts::time_manager::GameClock;
+use crate::mcts::tree::SearchInfo;
 use lichess_api::model::board::stream::events::GameEventInfo;
-use lichess_api::model::Color as ApiColor;
-use log::debug;
-use std::boxed::Box;
-use std::cell::RefCell;
-use std::pin::Pin;
-use std::rc::Rc;
+use lichess_api::model::board::stream::game::GameState;
+use lichess_api::model::Speed;
+use log::{debug, warn};
 
 use std::fmt;
 
-pub struct BotGame {
+pub struct BotGame<E: Engine<E>> {
     id: String,
-    game: Rc<RefCell<chess::Game>>,
-    searcher: mcts::search::Searcher,
+    game: Arc<Mutex<chess::Game>>,
+    searcher: E,
     pub bot_is: chess::Color,
+    // Correspondence and unlimited games have no running clock
+    timed: bool,
+    // Search parameters of the game's speed, kept to rebuild the engine
+    config: SearchConfig,
+    // Raised to end the searches when the bot shuts down
+    stop_flag: Arc<AtomicBool>,
+    // Position the game started from and the moves played since, as known locally
+    initial_board: chess::Board,
+    moves: Vec<chess::ChessMove>,
 }
 
-impl BotGame {
-    pub fn new_from_challenge(game_info: &GameEventInfo) -> BotGame {
+/// Outcome of bringing the local game up to date with the online one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncStatus {
+    InSync,
+    /// The online state is outdated, a newer one follows
+    Lagging,
+    /// The online moves can not be replayed locally
+    Unplayable,
+}
+
+impl<E: Engine<E>> BotGame<E> {
+    pub fn new_from_challenge(
+        game_info: &GameEventInfo,
+        config: &SearchConfig,
+        stop_flag: Arc<AtomicBool>,
+    ) -> BotGame<E> {
         debug!("New game created with fen {:?}", &*game_info.fen);
-        let board =
-            chess::Board::from_str(&*game_info.fen).expect("Board could not be made from fen");
-        let game = Rc::new(RefCell::new(chess::Game::new_with_board(board)));
-        let searcher = mcts::search::Searcher::new(game.clone());
+        let board = board_from_fen(&game_info.fen).unwrap_or_else(|| {
+            warn!("Game {}: invalid fen {}", game_info.game_id, game_info.fen);
+            chess::Board::default()
+        });
+        let game = Arc::new(Mutex::new(chess::Game::new_with_board(board)));
+        let config = config.for_speed(&game_info.speed);
+        let mut searcher = E::new(game.clone());
+        searcher.configure(&config);
+        searcher.set_stop_flag(stop_flag.clone());
         BotGame {
             id: game_info.game_id.clone(),
-            game: game,
-            searcher: searcher,
+            game,
+            searcher,
             bot_is: api_to_chess_color(game_info.color.clone()),
+            timed: game_info.speed != Speed::Correspondence,
+            config,
+            stop_flag,
+            // The position of a resumed game is replaced by the initial one of its full state
+            initial_board: board,
+            moves: Vec::new(),
         }
     }
 
     pub fn botgame_side_to_move(&mut self) -> chess::Color {
-        let board = (*self.game).borrow().current_position();
+        let board = self.game.lock().unwrap().current_position();
         board.side_to_move()
     }
 
-    pub fn enter_opponent_move(&mut self, move_chain: &str) -> () {
-        let mut current_board = (*self.game).borrow().current_position();
-        // Compute online board
-        let mut default_board = chess::Board::default();
-        let mut online_board = chess::Board::default();
-        // Yield the online board by making all the chain moves from the default board
+    /// Sets the position the game started from, `None` or `startpos` for the standard one.
+    ///
+    /// A different position restarts the engine from it, the moves are replayed by the next
+    /// `sync_moves`.
+    pub fn set_initial_fen(&mut self, initial_fen: Option<&str>) {
+        let board = match initial_fen {
+            None | Some("startpos") => chess::Board::default(),
+            Some(fen) => match board_from_fen(fen) {
+                Some(board) => board,
+                None => {
+                    warn!("Game {}: invalid initial fen {fen}", self.id);
+                    return;
+                }
+            },
+        };
+        if board != self.initial_board {
+            debug!("Game {}: initial position set to {board}", self.id);
+            self.initial_board = board;
+            self.rebuild(&[]);
+        }
+    }
+
+    /// Brings the local game up to date with the moves played online, given as UCI moves
+    /// separated by spaces.
+    ///
+    /// Missing moves are handed to the engine one by one, a diverging history rebuilds the engine
+    /// from the initial position. An online history which lags behind the local one is taken for
+    /// an outdated state and ignored, unless `rewind` is set (e.g. after a takeback).
+    pub fn sync_moves(&mut self, move_chain: &str, rewind: bool) -> SyncStatus {
+        let online_moves = match self.parse_moves(move_chain) {
+            Ok(online_moves) => online_moves,
+            Err(error) => {
+                warn!("Game {}: unreadable moves {move_chain:?}: {error}", self.id);
+                return SyncStatus::Unplayable;
+            }
+        };
+        let n_common = self
+            .moves
+            .iter()
+            .zip(online_moves.iter())
+            .take_while(|(x, y)| x == y)
+            .count();
+        if n_common == self.moves.len() {
+            // Local history is a prefix of the online one
+            for chess_move in &online_moves[n_common..] {
+                let board = self.game.lock().unwrap().current_position();
+                if !board.legal(*chess_move) {
+                    warn!(
+                        "Game {}: move {chess_move} illegal in {board}, rebuilding",
+                        self.id
+                    );
+                    return self.rebuild_status(&online_moves);
+                }
+                debug!("Game {}: move {chess_move} entered", self.id);
+                self.searcher.provide_opponent_move(*chess_move);
+                self.moves.push(*chess_move);
+            }
+            SyncStatus::InSync
+        } else if n_common == online_moves.len() && !rewind {
+            debug!("Game {}: online moves lag behind, waiting", self.id);
+            SyncStatus::Lagging
+        } else {
+            if !rewind {
+                warn!(
+                    "Game {}: histories diverge after {n_common} moves, rebuilding",
+                    self.id
+                );
+            }
+            self.rebuild_status(&online_moves)
+        }
+    }
+
+    /// Reads UCI moves separated by spaces.
+    fn parse_moves(&self, move_chain: &str) -> Result<Vec<chess::ChessMove>, chess::Error> {
         move_chain
-            .split(" ")
-            .map(|mv| chess::ChessMove::from_str(mv).expect(&format!("{mv} is no valid ChessMove")))
-            .for_each(|mv| {
-                std::mem::swap(&mut default_board, &mut online_board);
-                default_board.make_move(mv, &mut online_board);
-            });
-        // If both local and online boards different:
-        if current_board != online_board {
-            debug!("Board adjustment necesary, offline board is behind");
-            let moves_iterator = chess::MoveGen::new_legal(&current_board);
-            let last_opponent_move = move_chain
-                .split(" ")
-                .last()
-                .expect(&format!("No last move found in {}", move_chain));
-            let chess_move = chess::ChessMove::from_str(last_opponent_move).expect(&format!(
-                "Unable to gather last opponent move {last_opponent_move} from chain: {move_chain}"
-            ));
-            // Assert opponent's move legality, panics if not the case
-            assert!(
-                current_board.legal(chess_move),
-                "Move {:?} not in legal moves {:?}",
-                chess_move,
-                moves_iterator
-                    .map(|x| x.to_string())
-                    .collect::<Vec<String>>()
-            );
-            debug!("Opponent Move entered: {:?}", chess_move);
-            self.searcher.provide_opponent_move(chess_move);
+            .split(' ')
+            .filter(|x| !x.is_empty())
+            .map(chess::ChessMove::from_str)
+            .collect()
+    }
+
+    fn rebuild_status(&mut self, moves: &[chess::ChessMove]) -> SyncStatus {
+        if self.rebuild(moves) {
+            SyncStatus::InSync
         } else {
-            debug!("Board adjustment unnecesary, both online/offline boards same");
+            SyncStatus::Unplayable
+        }
+    }
+
+    /// Restarts the game and its engine from the initial position followed by `moves`. The search
+    /// tree of the former position is lost, the history deciding about draws and resigning is
+    /// kept.
+    ///
+    /// Returns whether all moves could be replayed.
+    fn rebuild(&mut self, moves: &[chess::ChessMove]) -> bool {
+        debug!("Game {}: rebuilding after {} moves", self.id, moves.len());
+        let mut game = chess::Game::new_with_board(self.initial_board);
+        self.moves.clear();
+        let mut is_replayed = true;
+        for chess_move in moves {
+            if !game.make_move(*chess_move) {
+                warn!(
+                    "Game {}: move {chess_move} illegal in {}, unable to replay the game",
+                    self.id,
+                    game.current_position()
+                );
+                is_replayed = false;
+                break;
+            }
+            self.moves.push(*chess_move);
         }
+        self.game = Arc::new(Mutex::new(game));
+        let adjudicator = self.searcher.adjudicator();
+        self.searcher = E::new(self.game.clone());
+        self.searcher.configure(&self.config);
+        self.searcher.set_stop_flag(self.stop_flag.clone());
+        self.searcher.set_adjudicator(adjudicator);
+        is_replayed
+    }
+
+    /// Hands the players' remaining times to the engine before it searches its next move.
+    pub fn update_clock(&mut self, game_state: &GameState) {
+        let clock = if self.timed {
+            Some(GameClock::from(game_state))
+        } else {
+            None
+        };
+        self.searcher.set_clock(clock);
     }
 
     pub fn get_fen(&self) -> String {
-        (*self.game).borrow().current_position().to_string().clone()
+        self.game.lock().unwrap().current_position().to_string()
+    }
+
+    /// Whether the engine takes the draw offered by the opponent.
+    pub fn accepts_draw(&self) -> bool {
+        self.searcher.accepts_draw()
+    }
+
+    pub fn last_search_info(&self) -> Option<SearchInfo> {
+        self.searcher.last_search_info()
     }
 }
 
-pub fn yield_next_move(
-    bot_game: Arc<Mutex<crate::game::BotGame>>,
-    mut api: Arc<ApiHandler>,
-) -> (String, bool) {
+/// What the bot does on its turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotAction {
+    Move {
+        chess_move: String,
+        offer_draw: bool,
+    },
+    Resign,
+}
+
+/// Searches the next move on the thread pool, so that the async runtime keeps serving other games.
+pub async fn yield_next_move<E: Engine<E> + 'static>(
+    bot_game: Arc<Mutex<crate::game::BotGame<E>>>,
+    api: Arc<ApiHandler<E>>,
+) -> BotAction {
     debug!("Yield next move called");
-    let next_move_receiver = api
-        .pool
-        .schedule_job(move || _yield_next_move(bot_game.clone()));
-    let game_move = next_move_receiver.recv().unwrap();
-    debug!("Next move {game_move} unwrapped from receiver");
-    let offer_draw_flag = false;
-    (game_move, offer_draw_flag)
+    let next_move_receiver = api.pool.schedule_job(move || _yield_next_move(bot_game));
+    // The game can not be played on without its search, resigning ends it cleanly
+    let Ok(action) = next_move_receiver.await else {
+        warn!("Search job panicked before yielding a move");
+        return BotAction::Resign;
+    };
+    debug!("Next action {action:?} unwrapped from receiver");
+    action
 }
 
-fn _yield_next_move(bot_game: Arc<Mutex<BotGame>>) -> String {
+fn _yield_next_move<E: Engine<E>>(bot_game: Arc<Mutex<BotGame<E>>>) -> BotAction {
     debug!("_yield_next_move_called");
     let mut game_guard = bot_game.lock().unwrap();
     let bot_color = game_guard.bot_is;
-    let str_move = game_guard.searcher.get_next_move(bot_color);
+    let (chess_move, offer_draw) = match game_guard.searcher.get_next_move(bot_color) {
+        Ok(next_move) => next_move,
+        Err(error) => {
+            warn!("Game {}: no move found: {error}", game_guard.id);
+            return BotAction::Resign;
+        }
+    };
     debug!("Next move generated");
-    str_move.unwrap().0
-    // let curr_pos = (*game_guard.game).borrow().current_position();
-    // let moves_iterator = chess::MoveGen::new_legal(&curr_pos);
-    // let moves = moves_iterator.collect::<Vec<chess::ChessMove>>();
-    // let str_move = match moves.choose(&mut rand::thread_rng()){
-    //     Some(mv) => {
-    //         let str_move = format!("{}",mv);
-    //         (*game_guard.game).borrow_mut().make_move(*mv);
-    //         str_move
-    //     }
-    //     _ => "".to_string()
-    // };
-    // str_move
+    // The engine made the move on the shared game
+    match chess::ChessMove::from_str(&chess_move) {
+        Ok(played_move) => game_guard.moves.push(played_move),
+        Err(error) => warn!("Engine move {chess_move} unreadable: {error}"),
+    }
+    // The search statistics of the move decide about resigning
+    if game_guard.searcher.resigns() {
+        return BotAction::Resign;
+    }
+    BotAction::Move {
+        chess_move,
+        offer_draw,
+    }
 }
 
-impl fmt::Debug for BotGame {
+impl<E: Engine<E>> fmt::Debug for BotGame<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "BotGame: {}", self.id)
     }
 }
 
-unsafe impl Send for BotGame {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::adjudication::Adjudicator;
+    use crate::mcts::search::NoAvailableMoveError;
+
+    /// Engine entering the moves it is handed on the shared game, whose search never finds a move.
+    struct ReplayingEngine {
+        game: Arc<Mutex<chess::Game>>,
+        entered: Vec<chess::ChessMove>,
+        adjudicator: Adjudicator,
+    }
+
+    impl Engine<ReplayingEngine> for ReplayingEngine {
+        fn new(game: Arc<Mutex<chess::Game>>) -> ReplayingEngine {
+            ReplayingEngine {
+                game,
+                entered: Vec::new(),
+                adjudicator: Adjudicator::default(),
+            }
+        }
+
+        fn get_next_move(
+            &mut self,
+            _bot_color: chess::Color,
+        ) -> Result<(String, bool), NoAvailableMoveError> {
+            Err(NoAvailableMoveError {})
+        }
+
+        fn provide_opponent_move(&mut self, chess_move: chess::ChessMove) {
+            self.game.lock().unwrap().make_move(chess_move);
+            self.entered.push(chess_move);
+        }
+
+        fn adjudicator(&self) -> Adjudicator {
+            self.adjudicator.clone()
+        }
+
+        fn set_adjudicator(&mut self, adjudicator: Adjudicator) {
+            self.adjudicator = adjudicator;
+        }
+    }
+
+    fn bot_game(board: chess::Board) -> BotGame<ReplayingEngine> {
+        let game = Arc::new(Mutex::new(chess::Game::new_with_board(board)));
+        BotGame {
+            id: String::from("game"),
+            searcher: ReplayingEngine::new(game.clone()),
+            game,
+            bot_is: chess::Color::White,
+            timed: false,
+            config: SearchConfig::default(),
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            initial_board: board,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Board of `moves` played from `board`.
+    fn board_after(board: chess::Board, moves: &str) -> chess::Board {
+        moves.split_whitespace().fold(board, |board, x| {
+            board.make_move_new(chess::ChessMove::from_str(x).unwrap())
+        })
+    }
+
+    fn current_board(bot_game: &BotGame<ReplayingEngine>) -> chess::Board {
+        bot_game.game.lock().unwrap().current_position()
+    }
+
+    #[test]
+    fn missing_moves_are_entered_one_by_one() {
+        let mut bot_game = bot_game(chess::Board::default());
+        assert_eq!(bot_game.sync_moves("e2e4", false), SyncStatus::InSync);
+        let moves = "e2e4 e7e5 g1f3";
+        assert_eq!(bot_game.sync_moves(moves, false), SyncStatus::InSync);
+        // The engine kept its search and was handed each move once
+        assert_eq!(bot_game.searcher.entered.len(), 3);
+        assert_eq!(bot_game.moves.len(), 3);
+        assert_eq!(
+            current_board(&bot_game),
+            board_after(chess::Board::default(), moves)
+        );
+    }
+
+    #[test]
+    fn outdated_moves_are_lagging_unless_rewound() {
+        let mut bot_game = bot_game(chess::Board::default());
+        assert_eq!(bot_game.sync_moves("e2e4 e7e5", false), SyncStatus::InSync);
+        assert_eq!(bot_game.sync_moves("e2e4", false), SyncStatus::Lagging);
+        assert_eq!(bot_game.moves.len(), 2);
+        // A takeback rewinds the game to the online moves
+        assert_eq!(bot_game.sync_moves("e2e4", true), SyncStatus::InSync);
+        assert_eq!(bot_game.moves.len(), 1);
+        assert_eq!(
+            current_board(&bot_game),
+            board_after(chess::Board::default(), "e2e4")
+        );
+    }
+
+    #[test]
+    fn diverging_history_rebuilds_the_engine() {
+        let mut bot_game = bot_game(chess::Board::default());
+        assert_eq!(bot_game.sync_moves("e2e4 e7e5", false), SyncStatus::InSync);
+        let moves = "d2d4 d7d5 c2c4";
+        assert_eq!(bot_game.sync_moves(moves, false), SyncStatus::InSync);
+        // A new engine starts from the replayed position
+        assert!(bot_game.searcher.entered.is_empty());
+        assert_eq!(bot_game.moves.len(), 3);
+        assert_eq!(
+            current_board(&bot_game),
+            board_after(chess::Board::default(), moves)
+        );
+    }
+
+    #[test]
+    fn rebuilding_keeps_the_adjudication_history() {
+        let mut bot_game = bot_game(chess::Board::default());
+        let mut adjudicator = Adjudicator::default();
+        adjudicator.record(0.01);
+        adjudicator.record(0.02);
+        bot_game.searcher.adjudicator = adjudicator.clone();
+        assert_eq!(bot_game.sync_moves("e2e4 e7e5", false), SyncStatus::InSync);
+        assert_eq!(bot_game.sync_moves("d2d4", false), SyncStatus::InSync);
+        assert!(bot_game.searcher.entered.is_empty());
+        assert_eq!(bot_game.searcher.adjudicator, adjudicator);
+        bot_game.set_initial_fen(Some("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        assert_eq!(bot_game.searcher.adjudicator, adjudicator);
+    }
+
+    #[test]
+    fn illegal_or_unreadable_moves_are_unplayable() {
+        let mut bot_game = bot_game(chess::Board::default());
+        assert_eq!(bot_game.sync_moves("e2e5", false), SyncStatus::Unplayable);
+        assert_eq!(
+            bot_game.sync_moves("e2e4 e9", false),
+            SyncStatus::Unplayable
+        );
+        assert!(bot_game.moves.is_empty());
+    }
+
+    #[test]
+    fn moves_are_replayed_from_the_initial_fen() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let initial_board = chess::Board::from_str(fen).unwrap();
+        let mut bot_game = bot_game(chess::Board::default());
+        bot_game.set_initial_fen(Some(fen));
+        assert_eq!(current_board(&bot_game), initial_board);
+        let moves = "e2e4 e8d7 e4e5";
+        assert_eq!(bot_game.sync_moves(moves, false), SyncStatus::InSync);
+        assert_eq!(current_board(&bot_game), board_after(initial_board, moves));
+        // The standard start position is read from the start fen
+        bot_game.set_initial_fen(Some("startpos"));
+        assert_eq!(current_board(&bot_game), chess::Board::default());
+    }
+
+    #[test]
+    fn failed_search_resigns_the_game() {
+        let bot_game = bot_game(chess::Board::default());
+        let action = _yield_next_move(Arc::new(Mutex::new(bot_game)));
+        assert!(matches!(action, BotAction::Resign));
+    }
+}
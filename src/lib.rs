@@ -0,0 +1,21 @@
+pub mod api;
+pub mod challenge;
+pub mod chat;
+pub mod config;
+pub mod game;
+pub mod matchmaking;
+pub mod mcts;
+pub mod runner;
+pub mod uci;
+pub mod utils;
+
+pub use crate::runner::BotRunner;
+
+use crate::mcts::search::Searcher;
+
+/// Runs the bot with the default MCTS engine until the event stream ends.
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let runner = BotRunner::<Searcher>::new()?;
+    runner.run().await;
+    Ok(())
+}
@@ -0,0 +1,431 @@
+use crate::api::ApiHandler;
+use crate::mcts::search::Engine;
+use lichess_api::model::challenges::{ChallengeBase, CreateChallenge};
+use lichess_api::model::users::{Perf, Perfs};
+use lichess_api::model::VariantKey;
+use log::{debug, info, warn};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+
+// Number of online bots fetched per matchmaking round
+const N_ONLINE_BOTS: u32 = 100;
+// Rating of an account which did not play a perf yet
+const DEFAULT_RATING: u32 = 1500;
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Initial time and increment of a challenge, in seconds.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockSetting {
+    pub initial: u32,
+    pub increment: u32,
+}
+
+impl ClockSetting {
+    /// Rating of a user in the perf Lichess rates a game with this clock in.
+    fn rating(&self, perfs: &Perfs) -> Option<u32> {
+        // Estimated game duration, as computed by Lichess
+        let duration = self.initial + 40 * self.increment;
+        let perf: &Option<Perf> = match duration {
+            0..=29 => &perfs.ultra_bullet,
+            30..=179 => &perfs.bullet,
+            180..=479 => &perfs.blitz,
+            480..=1499 => &perfs.rapid,
+            _ => &perfs.classical,
+        };
+        perf.as_ref().map(|x| x.rating)
+    }
+}
+
+/// Settings of the outgoing challenges sent to online bots.
+///
+/// Durations are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchmakingConfig {
+    pub enabled: bool,
+    /// Time between two matchmaking rounds
+    pub interval: u64,
+    /// Clocks to challenge with, one is picked at random per challenge
+    pub time_controls: Vec<ClockSetting>,
+    pub rated: bool,
+    /// Largest difference between the opponent's rating and the bot's one
+    pub max_rating_difference: u32,
+    /// Time after which an unanswered challenge is canceled
+    pub challenge_timeout: u64,
+    /// Time during which a bot which declined a challenge is not challenged again
+    pub decline_cooldown: u64,
+    /// Maximal number of games started by matchmaking within 24 hours
+    pub daily_game_quota: usize,
+}
+
+impl Default for MatchmakingConfig {
+    fn default() -> MatchmakingConfig {
+        MatchmakingConfig {
+            enabled: false,
+            interval: 60,
+            time_controls: vec![
+                ClockSetting {
+                    initial: 180,
+                    increment: 2,
+                },
+                ClockSetting {
+                    initial: 300,
+                    increment: 3,
+                },
+            ],
+            rated: true,
+            max_rating_difference: 300,
+            challenge_timeout: 60,
+            decline_cooldown: 3600,
+            daily_game_quota: 100,
+        }
+    }
+}
+
+impl MatchmakingConfig {
+    /// Description of the first invalid setting, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            (self.interval >= 1, "interval must be at least 1"),
+            (
+                !self.time_controls.is_empty(),
+                "time_controls must not be empty",
+            ),
+            (
+                self.time_controls
+                    .iter()
+                    .all(|x| x.initial > 0 || x.increment > 0),
+                "time_controls must not contain a 0+0 clock",
+            ),
+            (
+                self.challenge_timeout >= 1,
+                "challenge_timeout must be at least 1",
+            ),
+        ];
+        match checks.iter().find(|(is_valid, _)| !is_valid) {
+            Some((_, message)) => Err(message.to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Outgoing challenges awaiting an answer and games started by matchmaking.
+#[derive(Debug, Default)]
+pub struct Matchmaker {
+    // Challenge id to opponent and creation time
+    pending: HashMap<String, (String, Instant)>,
+    // Opponent to time of the last declined challenge
+    declined_by: HashMap<String, Instant>,
+    // Start times of the games of the last 24 hours
+    games_started: VecDeque<Instant>,
+}
+
+impl Matchmaker {
+    pub fn n_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// A game with the id of a pending challenge means the challenge was accepted.
+    pub fn on_game_start(&mut self, game_id: &str) {
+        if self.pending.remove(game_id).is_some() {
+            self.games_started.push_back(Instant::now());
+        }
+    }
+
+    pub fn on_challenge_created(&mut self, challenge_id: String, opponent: String) {
+        self.pending
+            .insert(challenge_id, (opponent, Instant::now()));
+    }
+
+    pub fn on_challenge_declined(&mut self, challenge_id: &str) {
+        if let Some((opponent, _)) = self.pending.remove(challenge_id) {
+            self.declined_by.insert(opponent, Instant::now());
+        }
+    }
+
+    pub fn on_challenge_canceled(&mut self, challenge_id: &str) {
+        self.pending.remove(challenge_id);
+    }
+
+    fn games_today(&mut self) -> usize {
+        while let Some(start) = self.games_started.front() {
+            if start.elapsed() < DAY {
+                break;
+            }
+            self.games_started.pop_front();
+        }
+        self.games_started.len()
+    }
+
+    /// Removes and returns the challenges older than `timeout`.
+    fn take_stale(&mut self, timeout: Duration) -> Vec<String> {
+        let stale = self
+            .pending
+            .iter()
+            .filter(|(_, (_, created))| created.elapsed() >= timeout)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<String>>();
+        stale.iter().for_each(|id| _ = self.pending.remove(id));
+        stale
+    }
+
+    fn is_challenged(&self, opponent: &str) -> bool {
+        self.pending
+            .values()
+            .any(|(x, _)| x.eq_ignore_ascii_case(opponent))
+    }
+
+    fn recently_declined(&self, opponent: &str, cooldown: Duration) -> bool {
+        self.declined_by
+            .get(opponent)
+            .is_some_and(|x| x.elapsed() < cooldown)
+    }
+
+    /// Whether the bot `user`, rated `rating`, may challenge `opponent`, rated `opponent_rating`
+    /// in the same perf (`None` if it did not play it yet). Bots already challenged or among the
+    /// opponents of the bot's games `playing` are not challenged again.
+    fn is_eligible(
+        &self,
+        config: &MatchmakingConfig,
+        user: &str,
+        rating: u32,
+        opponent: &str,
+        opponent_rating: Option<u32>,
+        playing: &[String],
+    ) -> bool {
+        let is_in_band =
+            opponent_rating.is_some_and(|x| x.abs_diff(rating) <= config.max_rating_difference);
+        let cooldown = Duration::from_secs(config.decline_cooldown);
+        is_in_band
+            && !opponent.eq_ignore_ascii_case(user)
+            && !self.recently_declined(opponent, cooldown)
+            && !self.is_challenged(opponent)
+            && !playing.iter().any(|x| x.eq_ignore_ascii_case(opponent))
+    }
+}
+
+/// Challenges an online bot every `interval` seconds, as long as the bot has room for a game
+/// and is not shutting down.
+pub async fn matchmaking_loop<E: Engine<E> + 'static>(api_handle: Arc<ApiHandler<E>>) {
+    let config = api_handle.bot_config.matchmaking.clone();
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+    loop {
+        interval.tick().await;
+        if api_handle.is_shutting_down() {
+            return;
+        }
+        cancel_stale_challenges(&api_handle, Duration::from_secs(config.challenge_timeout)).await;
+        let n_pending = api_handle.matchmaker.lock().unwrap().n_pending();
+        let games_today = api_handle.matchmaker.lock().unwrap().games_today();
+        if api_handle.active_games() + n_pending >= api_handle.bot_config.max_concurrent_games {
+            continue;
+        }
+        if games_today >= config.daily_game_quota {
+            debug!(
+                "Daily matchmaking quota of {} games reached",
+                config.daily_game_quota
+            );
+            continue;
+        }
+        challenge_online_bot(&api_handle, &config).await;
+    }
+}
+
+/// Cancels the pending challenges older than `timeout`, all of them for a zero timeout.
+pub async fn cancel_stale_challenges<E: Engine<E>>(api_handle: &ApiHandler<E>, timeout: Duration) {
+    let stale = api_handle.matchmaker.lock().unwrap().take_stale(timeout);
+    for challenge_id in stale {
+        debug!("Canceling unanswered challenge {challenge_id}");
+        let request =
+            lichess_api::model::challenges::cancel::PostRequest::new(challenge_id.clone(), None);
+        if let Err(error) = api_handle.lichess_api.cancel_challenge(request).await {
+            warn!("Unable to cancel challenge {challenge_id}: {error}");
+        }
+    }
+}
+
+/// Sends a challenge to a random online bot rated within the configured band.
+async fn challenge_online_bot<E: Engine<E>>(
+    api_handle: &ApiHandler<E>,
+    config: &MatchmakingConfig,
+) {
+    let clock = *config
+        .time_controls
+        .choose(&mut rand::thread_rng())
+        .expect("Matchmaking time controls were validated on load");
+    let profile_request = lichess_api::model::account::profile::GetRequest::new();
+    let rating = match api_handle.lichess_api.get_profile(profile_request).await {
+        Ok(profile) => clock.rating(&profile.user.perfs).unwrap_or(DEFAULT_RATING),
+        Err(error) => {
+            warn!("Unable to fetch the bot's profile: {error}");
+            return;
+        }
+    };
+    let online_request = lichess_api::model::bot::online::GetRequest::new(N_ONLINE_BOTS);
+    let mut online_bots = match api_handle.lichess_api.bot_get_online(online_request).await {
+        Ok(online_bots) => online_bots,
+        Err(error) => {
+            warn!("Unable to fetch online bots: {error}");
+            return;
+        }
+    };
+    let playing = api_handle.opponents();
+    let mut candidates = Vec::<String>::new();
+    while let Some(bot) = online_bots.next().await {
+        // Bots whose profile can not be read are skipped
+        let Ok(bot) = bot else { continue };
+        if api_handle.matchmaker.lock().unwrap().is_eligible(
+            config,
+            &api_handle.user,
+            rating,
+            &bot.username,
+            clock.rating(&bot.perfs),
+            &playing,
+        ) {
+            candidates.push(bot.username);
+        }
+    }
+    let Some(opponent) = candidates.choose(&mut rand::thread_rng()).cloned() else {
+        debug!(
+            "No online bot to challenge within {} of {rating}",
+            config.max_rating_difference
+        );
+        return;
+    };
+    let challenge = CreateChallenge {
+        base: ChallengeBase {
+            clock_limit: Some(clock.initial),
+            clock_increment: Some(clock.increment),
+            days: None,
+            variant: VariantKey::Standard,
+            fen: None,
+        },
+        rated: config.rated,
+        keep_alive_stream: false,
+        accept_by_token: None,
+        message: None,
+        rules: String::new(),
+    };
+    let request = lichess_api::model::challenges::create::PostRequest::new(&opponent, challenge);
+    match api_handle.lichess_api.create_challenge(request).await {
+        Ok(created) => {
+            info!(
+                "Challenged {opponent} to a {}+{} game",
+                clock.initial, clock.increment
+            );
+            api_handle
+                .matchmaker
+                .lock()
+                .unwrap()
+                .on_challenge_created(created.challenge.base.id, opponent);
+        }
+        Err(error) => warn!("Unable to challenge {opponent}: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn is_eligible(matchmaker: &Matchmaker, opponent: &str, opponent_rating: Option<u32>) -> bool {
+        matchmaker.is_eligible(
+            &MatchmakingConfig::default(),
+            "brocciu",
+            1500,
+            opponent,
+            opponent_rating,
+            &[],
+        )
+    }
+
+    #[test]
+    fn picks_opponents_within_the_rating_band() {
+        let matchmaker = Matchmaker::default();
+        assert!(is_eligible(&matchmaker, "rival", Some(1800)));
+        assert!(is_eligible(&matchmaker, "rival", Some(1200)));
+        assert!(!is_eligible(&matchmaker, "rival", Some(1801)));
+        // Unrated in the perf of the challenge
+        assert!(!is_eligible(&matchmaker, "rival", None));
+    }
+
+    #[test]
+    fn never_picks_itself() {
+        assert!(!is_eligible(&Matchmaker::default(), "Brocciu", Some(1500)));
+    }
+
+    #[test]
+    fn skips_opponents_which_declined_recently() {
+        let mut matchmaker = Matchmaker::default();
+        matchmaker.on_challenge_created("c1".to_string(), "rival".to_string());
+        matchmaker.on_challenge_declined("c1");
+        assert_eq!(matchmaker.n_pending(), 0);
+        assert!(!is_eligible(&matchmaker, "rival", Some(1500)));
+        assert!(matchmaker.recently_declined("rival", HOUR));
+        assert!(!matchmaker.recently_declined("rival", Duration::ZERO));
+        // A canceled challenge is no decline
+        matchmaker.on_challenge_created("c2".to_string(), "other".to_string());
+        matchmaker.on_challenge_canceled("c2");
+        assert!(is_eligible(&matchmaker, "other", Some(1500)));
+    }
+
+    #[test]
+    fn skips_opponents_already_challenged_or_played() {
+        let mut matchmaker = Matchmaker::default();
+        matchmaker.on_challenge_created("c1".to_string(), "Rival".to_string());
+        assert!(!is_eligible(&matchmaker, "rival", Some(1500)));
+        assert!(is_eligible(&matchmaker, "other", Some(1500)));
+        // Once the challenge is accepted, the game's opponent is skipped instead
+        matchmaker.on_game_start("c1");
+        assert!(is_eligible(&matchmaker, "rival", Some(1500)));
+        let config = MatchmakingConfig::default();
+        let playing = ["Rival".to_string()];
+        assert!(!matchmaker.is_eligible(&config, "brocciu", 1500, "rival", Some(1500), &playing));
+    }
+
+    #[test]
+    fn counts_accepted_challenges_only() {
+        let mut matchmaker = Matchmaker::default();
+        matchmaker.on_challenge_created("c1".to_string(), "rival".to_string());
+        matchmaker.on_challenge_created("c2".to_string(), "other".to_string());
+        assert_eq!(matchmaker.n_pending(), 2);
+        matchmaker.on_game_start("c1");
+        // Games challenged by others do not count
+        matchmaker.on_game_start("g1");
+        assert_eq!(matchmaker.n_pending(), 1);
+        assert_eq!(matchmaker.games_today(), 1);
+    }
+
+    #[test]
+    fn takes_stale_challenges() {
+        let mut matchmaker = Matchmaker::default();
+        matchmaker.on_challenge_created("c1".to_string(), "rival".to_string());
+        assert!(matchmaker.take_stale(HOUR).is_empty());
+        assert_eq!(
+            matchmaker.take_stale(Duration::ZERO),
+            vec!["c1".to_string()]
+        );
+        assert_eq!(matchmaker.n_pending(), 0);
+    }
+
+    #[test]
+    fn rates_clocks_in_their_lichess_perf() {
+        let perfs: Perfs = serde_json::from_value(json!({
+            "bullet": {"games": 10, "rating": 1400, "rd": 60, "prog": 0},
+            "blitz": {"games": 10, "rating": 1600, "rd": 60, "prog": 0}
+        }))
+        .unwrap();
+        let rating = |initial, increment| ClockSetting { initial, increment }.rating(&perfs);
+        assert_eq!(rating(60, 0), Some(1400));
+        // 3+2 lasts 180 + 40 * 2 seconds, a blitz game
+        assert_eq!(rating(180, 2), Some(1600));
+        assert_eq!(rating(600, 5), None);
+    }
+}
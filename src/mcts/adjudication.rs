@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+/// When to offer or accept a draw, based on the win ratio of the bot's last searches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DrawPolicy {
+    /// Offer a draw along with the bot's moves
+    pub offer: bool,
+    /// Accept the draws offered by the opponent
+    pub accept: bool,
+    /// Band of win ratios considered as a drawn position
+    pub min_win_ratio: f32,
+    pub max_win_ratio: f32,
+    /// Consecutive searches whose win ratio has to lie within the band
+    pub n_moves: usize,
+    /// Moves of the bot before which no draw is offered
+    pub min_game_moves: usize,
+}
+
+impl Default for DrawPolicy {
+    fn default() -> DrawPolicy {
+        DrawPolicy {
+            offer: true,
+            accept: true,
+            min_win_ratio: 0.4,
+            max_win_ratio: 0.6,
+            n_moves: 10,
+            min_game_moves: 30,
+        }
+    }
+}
+
+/// When to resign, based on the win ratio of the bot's last searches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResignPolicy {
+    pub enabled: bool,
+    /// Win ratio below which the game is considered lost
+    pub max_win_ratio: f32,
+    /// Consecutive searches whose win ratio has to lie below the threshold
+    pub n_moves: usize,
+}
+
+impl Default for ResignPolicy {
+    fn default() -> ResignPolicy {
+        ResignPolicy {
+            enabled: true,
+            max_win_ratio: 0.05,
+            n_moves: 5,
+        }
+    }
+}
+
+/// Win ratios of the bot's searches in a game, from its own point of view.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Adjudicator {
+    win_ratios: Vec<f32>,
+}
+
+impl Adjudicator {
+    pub fn record(&mut self, win_ratio: f32) {
+        self.win_ratios.push(win_ratio);
+    }
+
+    /// Whether the last `n` recorded win ratios all satisfy `condition`.
+    fn last_n_all(&self, n: usize, condition: impl Fn(f32) -> bool) -> bool {
+        self.win_ratios.len() >= n.max(1)
+            && self.win_ratios[self.win_ratios.len() - n.max(1)..]
+                .iter()
+                .all(|x| condition(*x))
+    }
+
+    pub fn offers_draw(&self, policy: &DrawPolicy) -> bool {
+        policy.offer
+            && self.win_ratios.len() >= policy.min_game_moves
+            && self.last_n_all(policy.n_moves, |x| {
+                (policy.min_win_ratio..=policy.max_win_ratio).contains(&x)
+            })
+    }
+
+    /// A drawn or worse position makes the bot accept, it keeps playing for a win otherwise.
+    pub fn accepts_draw(&self, policy: &DrawPolicy) -> bool {
+        policy.accept && self.last_n_all(policy.n_moves, |x| x <= policy.max_win_ratio)
+    }
+
+    pub fn resigns(&self, policy: &ResignPolicy) -> bool {
+        policy.enabled && self.last_n_all(policy.n_moves, |x| x < policy.max_win_ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adjudicator(win_ratios: &[f32]) -> Adjudicator {
+        let mut adjudicator = Adjudicator::default();
+        for win_ratio in win_ratios {
+            adjudicator.record(*win_ratio);
+        }
+        adjudicator
+    }
+
+    fn draw_policy() -> DrawPolicy {
+        DrawPolicy {
+            n_moves: 3,
+            min_game_moves: 0,
+            ..DrawPolicy::default()
+        }
+    }
+
+    #[test]
+    fn draws_are_offered_after_n_moves_in_the_band() {
+        let policy = draw_policy();
+        assert!(!adjudicator(&[0.5, 0.5]).offers_draw(&policy));
+        assert!(adjudicator(&[0.9, 0.5, 0.45, 0.55]).offers_draw(&policy));
+        // A move outside the band resets the streak
+        let mut streak = adjudicator(&[0.5, 0.5, 0.5, 0.7]);
+        assert!(!streak.offers_draw(&policy));
+        streak.record(0.5);
+        streak.record(0.5);
+        assert!(!streak.offers_draw(&policy));
+        streak.record(0.5);
+        assert!(streak.offers_draw(&policy));
+    }
+
+    #[test]
+    fn draws_are_offered_late_enough_and_if_enabled() {
+        let streak = adjudicator(&[0.5; 5]);
+        let policy = DrawPolicy {
+            min_game_moves: 6,
+            ..draw_policy()
+        };
+        assert!(!streak.offers_draw(&policy));
+        let policy = DrawPolicy {
+            offer: false,
+            ..draw_policy()
+        };
+        assert!(!streak.offers_draw(&policy));
+    }
+
+    #[test]
+    fn draws_are_accepted_in_drawn_or_worse_positions() {
+        let policy = draw_policy();
+        assert!(adjudicator(&[0.5, 0.1, 0.3]).accepts_draw(&policy));
+        assert!(!adjudicator(&[0.5, 0.7, 0.3]).accepts_draw(&policy));
+        assert!(!adjudicator(&[0.5, 0.5]).accepts_draw(&policy));
+    }
+
+    #[test]
+    fn resigning_needs_n_consecutive_lost_moves() {
+        let policy = ResignPolicy {
+            n_moves: 3,
+            ..ResignPolicy::default()
+        };
+        assert!(!adjudicator(&[0.01, 0.01]).resigns(&policy));
+        assert!(!adjudicator(&[0.01, 0.2, 0.01, 0.01]).resigns(&policy));
+        assert!(adjudicator(&[0.2, 0.01, 0.01, 0.01]).resigns(&policy));
+        let policy = ResignPolicy {
+            enabled: false,
+            ..policy
+        };
+        assert!(!adjudicator(&[0.01; 3]).resigns(&policy));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::mcts::adjudication::{DrawPolicy, ResignPolicy};
+use crate::mcts::rave::{RaveConfig, RaveSchedule};
+use crate::mcts::search::MAX_SEARCH_DEPTH;
+use crate::mcts::time_manager::TimeManager;
+use crate::mcts::tree::{
+    MoveSelection, ParallelMode, SelectionPolicy, SimulationPolicy, Tree, DEFAULT_MAX_SEARCH_TIME,
+    DEFAULT_N_CUTOFF_MOVES, DEFAULT_PUCT_CONSTANT, DEFAULT_UCT_CONSTANT, DEFAULT_VIRTUAL_LOSS,
+};
+use crate::utils::parse_args::{apply_override, merge};
+use core::fmt;
+use lichess_api::model::Speed;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Search parameters of the MCTS engine, read from `configs/default_search.json`.
+///
+/// Missing fields take their default value. `time_controls` maps a Lichess speed
+/// (`ultraBullet`, `bullet`, `blitz`, `rapid`, `classical`, `correspondence`) to fields
+/// overriding the ones above in games of that speed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub max_search_depth: usize,
+    pub n_cutoff_moves: usize,
+    /// Search time per move in seconds in games without a clock, e.g. correspondence games
+    pub max_search_time: f32,
+    pub selection_policy: SelectionPolicy,
+    pub simulation_policy: SimulationPolicy,
+    pub c_uct: f32,
+    pub c_puct: f32,
+    pub n_threads: usize,
+    pub parallel_mode: ParallelMode,
+    pub virtual_loss: usize,
+    pub early_stop: bool,
+    pub move_selection: MoveSelection,
+    pub rave: RaveConfig,
+    pub time_manager: TimeManager,
+    pub draw: DrawPolicy,
+    pub resign: ResignPolicy,
+    pub time_controls: BTreeMap<String, Map<String, Value>>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            max_search_depth: MAX_SEARCH_DEPTH,
+            n_cutoff_moves: DEFAULT_N_CUTOFF_MOVES,
+            max_search_time: DEFAULT_MAX_SEARCH_TIME,
+            selection_policy: SelectionPolicy::default(),
+            simulation_policy: SimulationPolicy::default(),
+            c_uct: DEFAULT_UCT_CONSTANT,
+            c_puct: DEFAULT_PUCT_CONSTANT,
+            n_threads: 1,
+            parallel_mode: ParallelMode::default(),
+            virtual_loss: DEFAULT_VIRTUAL_LOSS,
+            early_stop: true,
+            move_selection: MoveSelection::default(),
+            rave: RaveConfig::default(),
+            time_manager: TimeManager::default(),
+            draw: DrawPolicy::default(),
+            resign: ResignPolicy::default(),
+            time_controls: BTreeMap::new(),
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Parses and validates a configuration, applying `overrides` (`key=value`, nested keys
+    /// separated by dots) on top of the JSON document.
+    pub fn from_json(json: &str, overrides: &[String]) -> Result<SearchConfig, SearchConfigError> {
+        let mut value: Value = serde_json::from_str(json)
+            .map_err(|x| SearchConfigError::new(format!("malformed json: {x}")))?;
+        for entry in overrides {
+            apply_override(&mut value, entry).map_err(SearchConfigError::new)?;
+        }
+        let config = deserialize(value).map_err(SearchConfigError::new)?;
+        config.validate()?;
+        // Fail at startup rather than when a game of the given speed starts
+        for time_control in config.time_controls.keys() {
+            config
+                .with_overrides(time_control)?
+                .validate()
+                .map_err(|x| {
+                    SearchConfigError::new(format!("time_controls.{time_control}: {}", x.message))
+                })?;
+        }
+        Ok(config)
+    }
+
+    /// Configuration of games played at `speed`.
+    pub fn for_speed(&self, speed: &Speed) -> SearchConfig {
+        let time_control = match serde_json::to_value(speed) {
+            Ok(Value::String(time_control)) => time_control,
+            _ => return self.clone(),
+        };
+        if !self.time_controls.contains_key(&time_control) {
+            return self.clone();
+        }
+        // Overrides are validated when the configuration is loaded
+        self.with_overrides(&time_control)
+            .expect("Time control overrides were validated on load")
+    }
+
+    /// Hands the tree parameters over to `tree`.
+    pub fn apply(&self, tree: &mut Tree) {
+        tree.set_max_search_depth(self.max_search_depth);
+        tree.set_n_cutoff_moves(self.n_cutoff_moves);
+        tree.set_max_search_time(self.max_search_time);
+        tree.set_selection_policy(self.selection_policy);
+        tree.set_simulation_policy(self.simulation_policy);
+        tree.set_c_uct(self.c_uct);
+        tree.set_c_puct(self.c_puct);
+        tree.set_n_threads(self.n_threads);
+        tree.set_parallel_mode(self.parallel_mode);
+        tree.set_virtual_loss(self.virtual_loss);
+        tree.set_early_stop(self.early_stop);
+        tree.set_move_selection(self.move_selection);
+        tree.set_rave(self.rave.enabled.then_some(self.rave.schedule));
+    }
+
+    fn with_overrides(&self, time_control: &str) -> Result<SearchConfig, SearchConfigError> {
+        let context =
+            |x: String| SearchConfigError::new(format!("time_controls.{time_control}: {x}"));
+        serde_json::from_value::<Speed>(Value::String(time_control.to_string()))
+            .map_err(|_| context("unknown time control".to_string()))?;
+        let mut value = serde_json::to_value(self).map_err(|x| context(x.to_string()))?;
+        merge(
+            &mut value,
+            Value::Object(self.time_controls[time_control].clone()),
+        );
+        let mut config = deserialize(value).map_err(context)?;
+        // Overrides do not nest
+        config.time_controls.clear();
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), SearchConfigError> {
+        let checks = [
+            (
+                self.max_search_depth >= 1,
+                "max_search_depth must be at least 1",
+            ),
+            (
+                self.max_search_time > 0_f32,
+                "max_search_time must be a positive number of seconds",
+            ),
+            (
+                self.c_uct >= 0_f32 && self.c_uct.is_finite(),
+                "c_uct must be a non-negative number",
+            ),
+            (
+                self.c_puct >= 0_f32 && self.c_puct.is_finite(),
+                "c_puct must be a non-negative number",
+            ),
+            (self.n_threads >= 1, "n_threads must be at least 1"),
+            (
+                match self.simulation_policy {
+                    SimulationPolicy::EpsilonGreedy(epsilon) => (0_f32..=1_f32).contains(&epsilon),
+                    _ => true,
+                },
+                "the epsilon of EpsilonGreedy must lie between 0 and 1",
+            ),
+            (
+                match self.move_selection {
+                    MoveSelection::LowerConfidenceBound(c) => c >= 0_f32 && c.is_finite(),
+                    _ => true,
+                },
+                "the constant of LowerConfidenceBound must be a non-negative number",
+            ),
+            (
+                match self.rave.schedule {
+                    RaveSchedule::Equivalence(k) => k > 0_f32 && k.is_finite(),
+                    RaveSchedule::MinimumError(b) => b >= 0_f32 && b.is_finite(),
+                },
+                "rave.schedule must have a positive Equivalence or a non-negative MinimumError",
+            ),
+            (
+                self.time_manager.moves_to_go >= 1,
+                "time_manager.moves_to_go must be at least 1",
+            ),
+            (
+                self.time_manager.max_move_time > 0_f32,
+                "time_manager.max_move_time must be a positive number of seconds",
+            ),
+            (
+                0_f32 <= self.draw.min_win_ratio
+                    && self.draw.min_win_ratio <= self.draw.max_win_ratio
+                    && self.draw.max_win_ratio <= 1_f32,
+                "draw.min_win_ratio and draw.max_win_ratio must be ordered between 0 and 1",
+            ),
+            (self.draw.n_moves >= 1, "draw.n_moves must be at least 1"),
+            (
+                (0_f32..=1_f32).contains(&self.resign.max_win_ratio),
+                "resign.max_win_ratio must lie between 0 and 1",
+            ),
+            (
+                self.resign.n_moves >= 1,
+                "resign.n_moves must be at least 1",
+            ),
+        ];
+        match checks.iter().find(|(is_valid, _)| !is_valid) {
+            Some((_, message)) => Err(SearchConfigError::new(message.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Configuration of a JSON document, errors are prefixed by the first top-level field failing on
+/// its own as serde_json does not name the field of a value of the wrong type.
+fn deserialize(value: Value) -> Result<SearchConfig, String> {
+    serde_json::from_value(value.clone()).map_err(|error| {
+        let fails_alone = |field: &(&String, &Value)| {
+            let object = Map::from_iter([(field.0.clone(), field.1.clone())]);
+            serde_json::from_value::<SearchConfig>(Value::Object(object)).is_err()
+        };
+        match value.as_object().and_then(|x| x.iter().find(fails_alone)) {
+            Some((field, _)) => format!("{field}: {error}"),
+            None => error.to_string(),
+        }
+    })
+}
+
+#[derive(Debug)]
+pub struct SearchConfigError {
+    message: String,
+}
+
+impl SearchConfigError {
+    fn new(message: String) -> SearchConfigError {
+        SearchConfigError { message }
+    }
+}
+
+impl fmt::Display for SearchConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid search configuration: {}", self.message)
+    }
+}
+
+impl std::error::Error for SearchConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_SEARCH_CONFIG: &str = include_str!("../../configs/default_search.json");
+
+    fn error_message(json: &str, overrides: &[&str]) -> String {
+        let overrides: Vec<String> = overrides.iter().map(|x| x.to_string()).collect();
+        SearchConfig::from_json(json, &overrides)
+            .expect_err("Invalid configuration accepted")
+            .to_string()
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        let config = SearchConfig::from_json(DEFAULT_SEARCH_CONFIG, &[]).unwrap();
+        assert_eq!(config.max_search_depth, 15);
+        assert_eq!(config.n_cutoff_moves, 200);
+        assert_eq!(config.draw.n_moves, 10);
+        assert!(config.time_controls.contains_key("ultraBullet"));
+        // Missing fields keep their default
+        let config = SearchConfig::from_json("{}", &[]).unwrap();
+        assert_eq!(config.n_threads, SearchConfig::default().n_threads);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected_with_their_cause() {
+        assert!(error_message(r#"{"n_cutoff": 3}"#, &[]).contains("unknown field `n_cutoff`"));
+        assert!(error_message(r#"{"c_uct": "high"}"#, &[]).contains("c_uct: invalid type"));
+        assert!(error_message("{}", &["rave.schedule=Fast"]).contains("rave: unknown variant"));
+        assert!(error_message("{", &[]).contains("malformed json"));
+        assert!(error_message("{}", &["n_threads=0"]).contains("n_threads must be at least 1"));
+        assert!(error_message("{}", &["draw.min_win_ratio=0.7"]).contains("draw.min_win_ratio"));
+        assert!(error_message("{}", &["n_threads"]).contains("not key=value"));
+        // Time control overrides are checked on load
+        assert!(
+            error_message(r#"{"time_controls": {"blitz": {"n_threads": 0}}}"#, &[])
+                .contains("time_controls.blitz: n_threads must be at least 1")
+        );
+        assert!(
+            error_message(r#"{"time_controls": {"superfast": {}}}"#, &[])
+                .contains("time_controls.superfast: unknown time control")
+        );
+    }
+
+    #[test]
+    fn time_controls_override_the_config() {
+        let config = SearchConfig::from_json(DEFAULT_SEARCH_CONFIG, &[]).unwrap();
+        let ultra_bullet = config.for_speed(&Speed::UltraBullet);
+        assert_eq!(ultra_bullet.n_cutoff_moves, 50);
+        assert_eq!(
+            ultra_bullet.simulation_policy,
+            SimulationPolicy::CaptureFirst
+        );
+        assert_eq!(config.for_speed(&Speed::Bullet).n_cutoff_moves, 100);
+        // Speeds without overrides keep the base config
+        let blitz = config.for_speed(&Speed::Blitz);
+        assert_eq!(blitz.n_cutoff_moves, 200);
+        assert_eq!(blitz.simulation_policy, SimulationPolicy::Random);
+    }
+
+    #[test]
+    fn later_overrides_win() {
+        let overrides = [
+            "n_threads=2",
+            "rave.enabled=true",
+            "n_threads=4",
+            "time_controls.bullet.n_cutoff_moves=80",
+        ]
+        .map(String::from);
+        let config = SearchConfig::from_json(DEFAULT_SEARCH_CONFIG, &overrides).unwrap();
+        assert_eq!(config.n_threads, 4);
+        assert!(config.rave.enabled);
+        // Nested fields not overridden are kept
+        assert_eq!(config.rave.schedule, RaveSchedule::Equivalence(1000.0));
+        assert_eq!(config.for_speed(&Speed::Bullet).n_cutoff_moves, 80);
+        assert_eq!(config.for_speed(&Speed::Bullet).n_threads, 4);
+    }
+}
@@ -0,0 +1,301 @@
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_rook_moves, BitBoard, Board, Color,
+    MoveGen, Piece, Square, ALL_PIECES, EMPTY,
+};
+
+// Centipawn difference turning a 50% win probability into ~91%
+const WIN_PROBABILITY_SCALE: f32 = 400_f32;
+const MOBILITY_WEIGHT: i32 = 2;
+const PAWN_SHIELD_WEIGHT: i32 = 10;
+
+// Piece-square tables of the simplified evaluation function, seen from White, rank 8 first
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+/// Material value of a piece in centipawns.
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight => 320,
+        Piece::Bishop => 330,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
+
+/// Material balance in centipawns from White's point of view.
+pub fn material(board: &Board) -> i32 {
+    let white = board.color_combined(Color::White);
+    let black = board.color_combined(Color::Black);
+    ALL_PIECES
+        .iter()
+        .map(|piece| {
+            let pieces = board.pieces(*piece);
+            let balance = (pieces & white).popcnt() as i32 - (pieces & black).popcnt() as i32;
+            piece_value(*piece) * balance
+        })
+        .sum()
+}
+
+fn piece_square_value(piece: Piece, color: Color, square: Square) -> i32 {
+    let table = match piece {
+        Piece::Pawn => &PAWN_TABLE,
+        Piece::Knight => &KNIGHT_TABLE,
+        Piece::Bishop => &BISHOP_TABLE,
+        Piece::Rook => &ROOK_TABLE,
+        Piece::Queen => &QUEEN_TABLE,
+        Piece::King => &KING_TABLE,
+    };
+    let rank = square.get_rank().to_index();
+    let file = square.get_file().to_index();
+    // Tables start at rank 8, black pieces use the mirrored board
+    match color {
+        Color::White => table[(7 - rank) * 8 + file],
+        Color::Black => table[rank * 8 + file],
+    }
+}
+
+/// Piece-square table bonus in centipawns from White's point of view.
+pub fn piece_square(board: &Board) -> i32 {
+    ALL_PIECES
+        .iter()
+        .map(|piece| {
+            let pieces = board.pieces(*piece);
+            let white: i32 = (pieces & board.color_combined(Color::White))
+                .map(|square| piece_square_value(*piece, Color::White, square))
+                .sum();
+            let black: i32 = (pieces & board.color_combined(Color::Black))
+                .map(|square| piece_square_value(*piece, Color::Black, square))
+                .sum();
+            white - black
+        })
+        .sum()
+}
+
+/// Legal move count difference in centipawns from White's point of view.
+pub fn mobility(board: &Board) -> i32 {
+    let own_moves = MoveGen::new_legal(board).len() as i32;
+    // The opponent's moves are only available when not in check
+    let opponent_moves = match board.null_move() {
+        Some(null_move_board) => MoveGen::new_legal(&null_move_board).len() as i32,
+        None => own_moves,
+    };
+    let balance = MOBILITY_WEIGHT * (own_moves - opponent_moves);
+    match board.side_to_move() {
+        Color::White => balance,
+        Color::Black => -balance,
+    }
+}
+
+fn king_zone_attacks(board: &Board, color: Color) -> i32 {
+    let king_square = board.king_square(color);
+    let king_zone = get_king_moves(king_square) | BitBoard::from_square(king_square);
+    let blockers = *board.combined();
+    let opponent = board.color_combined(!color);
+    // Weight each opponent piece by how dangerous its attack on the king zone is
+    [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .iter()
+        .map(|piece| {
+            (board.pieces(*piece) & opponent)
+                .filter(|square| {
+                    let attacks = match piece {
+                        Piece::Knight => get_knight_moves(*square),
+                        Piece::Bishop => get_bishop_moves(*square, blockers),
+                        Piece::Rook => get_rook_moves(*square, blockers),
+                        _ => {
+                            get_bishop_moves(*square, blockers) | get_rook_moves(*square, blockers)
+                        }
+                    };
+                    attacks & king_zone != EMPTY
+                })
+                .count() as i32
+                * piece_value(*piece)
+                / 20
+        })
+        .sum()
+}
+
+fn pawn_shield(board: &Board, color: Color) -> i32 {
+    // Own pawns directly around and in front of the king
+    let king_square = board.king_square(color);
+    let shield_squares = get_king_moves(king_square)
+        .filter(|square| match color {
+            Color::White => square.get_rank() > king_square.get_rank(),
+            Color::Black => square.get_rank() < king_square.get_rank(),
+        })
+        .fold(EMPTY, |shield, square| {
+            shield | BitBoard::from_square(square)
+        });
+    let shield_pawns = shield_squares & board.pieces(Piece::Pawn) & board.color_combined(color);
+    PAWN_SHIELD_WEIGHT * shield_pawns.popcnt() as i32
+}
+
+/// Pawn shield bonus and king zone attack penalty in centipawns from White's point of view.
+pub fn king_safety(board: &Board) -> i32 {
+    let white = pawn_shield(board, Color::White) - king_zone_attacks(board, Color::White);
+    let black = pawn_shield(board, Color::Black) - king_zone_attacks(board, Color::Black);
+    white - black
+}
+
+/// Material and piece-square terms only, cheap enough to score every candidate move of a playout.
+pub fn evaluate_fast(board: &Board) -> i32 {
+    material(board) + piece_square(board)
+}
+
+/// Static evaluation in centipawns from White's point of view.
+pub fn evaluate(board: &Board) -> i32 {
+    material(board) + piece_square(board) + mobility(board) + king_safety(board)
+}
+
+/// Static evaluation in centipawns from `color`'s point of view.
+pub fn evaluate_for(board: &Board, color: Color) -> i32 {
+    match color {
+        Color::White => evaluate(board),
+        Color::Black => -evaluate(board),
+    }
+}
+
+/// Maps a centipawn score onto a win probability with a logistic curve.
+pub fn centipawns_to_win_probability(centipawns: i32) -> f32 {
+    1_f32 / (1_f32 + 10_f32.powf(-(centipawns as f32) / WIN_PROBABILITY_SCALE))
+}
+
+/// White's estimated win probability in the given position.
+pub fn win_probability(board: &Board) -> f32 {
+    centipawns_to_win_probability(evaluate(board))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const MIDDLEGAME: &str = "r1bqk2r/pp2bppp/2np1n2/4p3/2B1P3/2N2N2/PPP2PPP/R1BQ1RK1 w kq - 0 8";
+
+    /// Colors swapped and ranks mirrored, which negates every term of the evaluation.
+    fn mirrored(fen: &str) -> Board {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |x: &str| {
+            x.chars()
+                .map(|x| match x.is_ascii_uppercase() {
+                    true => x.to_ascii_lowercase(),
+                    false => x.to_ascii_uppercase(),
+                })
+                .collect::<String>()
+        };
+        let ranks: Vec<&str> = fields[0].split('/').rev().collect();
+        let side_to_move = if fields[1] == "w" { "b" } else { "w" };
+        let mirrored_fen = format!(
+            "{} {} {} - 0 1",
+            swap_case(&ranks.join("/")),
+            side_to_move,
+            swap_case(fields[2])
+        );
+        Board::from_str(&mirrored_fen).unwrap()
+    }
+
+    #[test]
+    fn material_favours_the_side_ahead() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        assert_eq!(material(&board), 900);
+        assert!(evaluate(&board) > 0);
+        assert!(evaluate_for(&board, Color::White) > 0);
+        assert!(evaluate_for(&board, Color::Black) < 0);
+        assert!(win_probability(&board) > 0.9);
+    }
+
+    #[test]
+    fn mirrored_positions_score_negated() {
+        for fen in [MIDDLEGAME, "4k3/8/8/8/8/8/8/Q3K3 w - - 0 1"] {
+            let board = Board::from_str(fen).unwrap();
+            assert_eq!(piece_square(&mirrored(fen)), -piece_square(&board));
+            assert_eq!(evaluate_fast(&mirrored(fen)), -evaluate_fast(&board));
+            assert_eq!(evaluate(&mirrored(fen)), -evaluate(&board));
+        }
+    }
+
+    #[test]
+    fn start_position_is_balanced() {
+        let board = Board::default();
+        assert!(evaluate(&board).abs() < 10);
+        assert!((win_probability(&board) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn win_probability_is_monotone_and_bounded() {
+        assert_eq!(centipawns_to_win_probability(0), 0.5);
+        let probabilities: Vec<f32> = (-20..=20)
+            .map(|x| centipawns_to_win_probability(100 * x))
+            .collect();
+        assert!(probabilities.windows(2).all(|x| x[0] < x[1]));
+        // Decided positions saturate without leaving the bounds
+        for centipawns in [i32::MIN, -100_000, 100_000, i32::MAX] {
+            assert!((0_f32..=1_f32).contains(&centipawns_to_win_probability(centipawns)));
+        }
+    }
+}
@@ -1,3 +1,10 @@
-pub mod node;
+pub mod adjudication;
+pub mod config;
+pub mod evaluation;
+pub mod outcome;
+pub mod playout;
+pub mod policy;
+pub mod rave;
 pub mod search;
+pub mod time_manager;
 pub mod tree;
@@ -0,0 +1,49 @@
+use chess::{Board, BoardStatus, Color};
+
+/// Result of a playout, shared by the simulation, the backpropagation and the win ratios of the
+/// tree nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameOutcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// Playout cut off, with White's estimated win probability
+    Estimated(f32),
+}
+
+impl GameOutcome {
+    /// Outcome of the game on `board`, `None` while it is ongoing.
+    pub fn from_board(board: &Board) -> Option<GameOutcome> {
+        match board.status() {
+            BoardStatus::Ongoing => None,
+            BoardStatus::Stalemate => Some(GameOutcome::Draw),
+            // The side to move is the one which got mated
+            BoardStatus::Checkmate => Some(GameOutcome::won_by(!board.side_to_move())),
+        }
+    }
+
+    pub fn won_by(color: Color) -> GameOutcome {
+        match color {
+            Color::White => GameOutcome::WhiteWins,
+            Color::Black => GameOutcome::BlackWins,
+        }
+    }
+
+    /// Score of White: 1 for a win, 0.5 for a draw and 0 for a loss.
+    pub fn white_score(&self) -> f32 {
+        match *self {
+            GameOutcome::WhiteWins => 1_f32,
+            GameOutcome::BlackWins => 0_f32,
+            GameOutcome::Draw => 0.5_f32,
+            GameOutcome::Estimated(white_win_probability) => white_win_probability,
+        }
+    }
+
+    /// Score of `color`, between 0 and 1.
+    pub fn score(&self, color: Color) -> f32 {
+        match color {
+            Color::White => self.white_score(),
+            Color::Black => 1_f32 - self.white_score(),
+        }
+    }
+}
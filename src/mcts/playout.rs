@@ -0,0 +1,178 @@
+use crate::mcts::evaluation::{evaluate_fast, piece_value};
+use crate::mcts::tree::SimulationPolicy;
+use chess::{Board, ChessMove, Color, MoveGen, EMPTY};
+use rand::Rng;
+
+/// Picks the next playout move on `board` following `simulation_policy`, `None` if no move is legal.
+pub fn choose_move(
+    board: &Board,
+    simulation_policy: SimulationPolicy,
+    rng: &mut impl Rng,
+) -> Option<ChessMove> {
+    match simulation_policy {
+        SimulationPolicy::Random => random_move(board, rng),
+        SimulationPolicy::CaptureFirst => {
+            best_capture(board, rng).or_else(|| random_move(board, rng))
+        }
+        SimulationPolicy::CheckFirst => checking_move(board, rng)
+            .or_else(|| best_capture(board, rng))
+            .or_else(|| random_move(board, rng)),
+        SimulationPolicy::EpsilonGreedy(epsilon) => {
+            if rng.gen::<f32>() < epsilon {
+                random_move(board, rng)
+            } else {
+                greedy_move(board, rng)
+            }
+        }
+    }
+}
+
+fn random_move(board: &Board, rng: &mut impl Rng) -> Option<ChessMove> {
+    let mut move_generator = MoveGen::new_legal(board);
+    let n_moves = move_generator.len();
+    if n_moves > 0 {
+        move_generator.nth(rng.gen_range(0, n_moves))
+    } else {
+        None
+    }
+}
+
+/// Most valuable victim / least valuable attacker ordering of a capture.
+fn mvv_lva(board: &Board, chess_move: ChessMove) -> i32 {
+    let victim = board.piece_on(chess_move.get_dest()).map_or(0, piece_value);
+    let attacker = board
+        .piece_on(chess_move.get_source())
+        .map_or(0, piece_value);
+    10 * victim - attacker
+}
+
+fn best_capture(board: &Board, rng: &mut impl Rng) -> Option<ChessMove> {
+    // Restrict the generator to moves landing on opponent pieces
+    let mut move_generator = MoveGen::new_legal(board);
+    move_generator.set_iterator_mask(*board.color_combined(!board.side_to_move()));
+    choose_best(move_generator, |x| mvv_lva(board, x), rng)
+}
+
+fn checking_move(board: &Board, rng: &mut impl Rng) -> Option<ChessMove> {
+    let checking_moves = MoveGen::new_legal(board)
+        .filter(|x| *board.make_move_new(*x).checkers() != EMPTY)
+        .collect::<Vec<ChessMove>>();
+    if checking_moves.is_empty() {
+        None
+    } else {
+        Some(checking_moves[rng.gen_range(0, checking_moves.len())])
+    }
+}
+
+fn greedy_move(board: &Board, rng: &mut impl Rng) -> Option<ChessMove> {
+    let side_to_move = board.side_to_move();
+    choose_best(
+        MoveGen::new_legal(board),
+        |x| {
+            let score = evaluate_fast(&board.make_move_new(x));
+            match side_to_move {
+                Color::White => score,
+                Color::Black => -score,
+            }
+        },
+        rng,
+    )
+}
+
+/// Argmax of `score` over `moves`, breaking ties uniformly at random.
+fn choose_best(
+    moves: impl Iterator<Item = ChessMove>,
+    score: impl Fn(ChessMove) -> i32,
+    rng: &mut impl Rng,
+) -> Option<ChessMove> {
+    let mut best_moves = Vec::<ChessMove>::new();
+    let mut best_score = i32::MIN;
+    for chess_move in moves {
+        let move_score = score(chess_move);
+        if move_score > best_score {
+            best_score = move_score;
+            best_moves.clear();
+        }
+        if move_score == best_score {
+            best_moves.push(chess_move);
+        }
+    }
+    if best_moves.is_empty() {
+        None
+    } else {
+        Some(best_moves[rng.gen_range(0, best_moves.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::str::FromStr;
+
+    // White may take the queen with the pawn or a pawn with the queen
+    const CAPTURES: &str = "4k3/8/8/3q3p/4P3/8/8/4K2Q w - - 0 1";
+    // Ra8+ is White's only check
+    const ONE_CHECK: &str = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+    const MATED: &str = "R3k3/8/4K3/8/8/8/8/8 b - - 0 1";
+
+    /// Moves picked on `fen` in a few tries of `simulation_policy`.
+    fn choices(fen: &str, simulation_policy: SimulationPolicy) -> Vec<Option<ChessMove>> {
+        let board = Board::from_str(fen).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..20)
+            .map(|_| choose_move(&board, simulation_policy, &mut rng))
+            .collect()
+    }
+
+    fn uci_move(uci: &str) -> Option<ChessMove> {
+        Some(ChessMove::from_str(uci).unwrap())
+    }
+
+    #[test]
+    fn random_moves_are_legal() {
+        let board = Board::from_str(CAPTURES).unwrap();
+        for chess_move in choices(CAPTURES, SimulationPolicy::Random) {
+            assert!(board.legal(chess_move.unwrap()));
+        }
+        assert!(choices(MATED, SimulationPolicy::Random)
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn capture_first_takes_the_most_valuable_victim() {
+        for chess_move in choices(CAPTURES, SimulationPolicy::CaptureFirst) {
+            assert_eq!(chess_move, uci_move("e4d5"));
+        }
+        // Without any capture, any move is played
+        assert!(choices(ONE_CHECK, SimulationPolicy::CaptureFirst)
+            .iter()
+            .all(Option::is_some));
+    }
+
+    #[test]
+    fn check_first_gives_check() {
+        for chess_move in choices(ONE_CHECK, SimulationPolicy::CheckFirst) {
+            assert_eq!(chess_move, uci_move("a1a8"));
+        }
+        // Captures come next, Qxh5 giving check in `CAPTURES`
+        for chess_move in choices(
+            "4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1",
+            SimulationPolicy::CheckFirst,
+        ) {
+            assert_eq!(chess_move, uci_move("e4d5"));
+        }
+    }
+
+    #[test]
+    fn greedy_moves_maximize_the_evaluation() {
+        for chess_move in choices(CAPTURES, SimulationPolicy::EpsilonGreedy(0_f32)) {
+            assert_eq!(chess_move, uci_move("e4d5"));
+        }
+        // Always random with epsilon 1
+        let random_choices = choices(CAPTURES, SimulationPolicy::EpsilonGreedy(1_f32));
+        assert!(random_choices.iter().any(|x| *x != uci_move("e4d5")));
+    }
+}
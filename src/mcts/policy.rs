@@ -0,0 +1,17 @@
+/// Supplies the prior probability of each legal move, used by the PUCT selection policy.
+///
+/// Providers are shared between the worker threads of a parallel search.
+pub trait PolicyProvider: Send + Sync {
+    /// Returns one prior per move, in the order of `moves`. Priors should sum up to 1.
+    fn priors(&self, board: &chess::Board, moves: &[chess::ChessMove]) -> Vec<f32>;
+}
+
+/// Assigns the same prior to every legal move.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UniformPolicy;
+
+impl PolicyProvider for UniformPolicy {
+    fn priors(&self, _board: &chess::Board, moves: &[chess::ChessMove]) -> Vec<f32> {
+        vec![1_f32 / moves.len() as f32; moves.len()]
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Equivalence parameter of the default `RaveSchedule`.
+pub const DEFAULT_RAVE_EQUIVALENCE: f32 = 1000_f32;
+
+/// Rapid Action Value Estimation, read from the `rave` entry of the search configuration.
+///
+/// Each move also gathers all-moves-as-first (AMAF) statistics: the outcomes of all playouts in
+/// which its player made the move later on, in the tree or in the playout. While selecting, the
+/// AMAF win ratio is blended into the move's own win ratio with the weight given by `schedule`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RaveConfig {
+    /// Off by default
+    pub enabled: bool,
+    pub schedule: RaveSchedule,
+}
+
+/// Weight of the AMAF win ratio of a move, fading as the move gathers visits of its own.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RaveSchedule {
+    /// `sqrt(k / (3 * visits + k))`: both win ratios weigh the same after `k` visits
+    Equivalence(f32),
+    /// `amaf_visits / (visits + amaf_visits + 4 * b^2 * visits * amaf_visits)`, minimizing the
+    /// error of the blend when the AMAF win ratio is biased by `b`
+    MinimumError(f32),
+}
+
+impl Default for RaveSchedule {
+    fn default() -> RaveSchedule {
+        RaveSchedule::Equivalence(DEFAULT_RAVE_EQUIVALENCE)
+    }
+}
+
+impl RaveSchedule {
+    /// Weight of the AMAF win ratio, between 0 and 1.
+    pub fn beta(&self, visits: usize, amaf_visits: usize) -> f32 {
+        let (n, n_amaf) = (visits as f32, amaf_visits as f32);
+        match *self {
+            RaveSchedule::Equivalence(k) => (k / (3_f32 * n + k)).sqrt(),
+            RaveSchedule::MinimumError(_) if amaf_visits == 0 => 0_f32,
+            RaveSchedule::MinimumError(b) => n_amaf / (n + n_amaf + 4_f32 * b * b * n * n_amaf),
+        }
+    }
+}
+
+impl fmt::Display for RaveSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RaveSchedule::Equivalence(k) => write!(f, "Equivalence({k})"),
+            RaveSchedule::MinimumError(b) => write!(f, "MinimumError({b})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equivalence_weight_fades_with_the_visits() {
+        let k = DEFAULT_RAVE_EQUIVALENCE;
+        let schedule = RaveSchedule::Equivalence(k);
+        assert_eq!(schedule.beta(0, 10), 1_f32);
+        let betas: Vec<f32> = (0..5_000)
+            .step_by(100)
+            .map(|x| schedule.beta(x, 10))
+            .collect();
+        assert!(betas.windows(2).all(|x| x[1] < x[0]));
+        assert!((schedule.beta(k as usize, 10) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn minimum_error_weight_fades_with_the_visits() {
+        let schedule = RaveSchedule::MinimumError(0.1);
+        assert_eq!(schedule.beta(0, 100), 1_f32);
+        assert_eq!(schedule.beta(10, 0), 0_f32);
+        let betas: Vec<f32> = (0..500)
+            .step_by(10)
+            .map(|x| schedule.beta(x, 100))
+            .collect();
+        assert!(betas.windows(2).all(|x| x[1] < x[0]));
+    }
+}
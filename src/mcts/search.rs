@@ -1,32 +1,77 @@
-use chess::ChessMove;
+use crate::mcts::adjudication::{Adjudicator, DrawPolicy, ResignPolicy};
+use crate::mcts::config::SearchConfig;
+use crate::mcts::time_manager::{GameClock, TimeManager};
+use crate::mcts::tree::{SearchInfo, DEFAULT_MAX_SEARCH_TIME};
 use core::fmt;
+use log::debug;
 use rand::prelude::SliceRandom;
-use rand::Error;
-use std::boxed::Box;
-use std::cell::RefCell;
-use std::pin::Pin;
-use std::rc::Rc;
-use std::{borrow::BorrowMut, io::Read};
-
-pub trait Engine<T> {
-    fn new(game: Rc<RefCell<chess::Game>>) -> T;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+/// Maximal tree depth explored below the current position.
+pub const MAX_SEARCH_DEPTH: usize = 15;
+
+/// Move search of a game, running on the search threads of the bot.
+pub trait Engine<T>: Send {
+    #[allow(clippy::new_ret_no_self)]
+    fn new(game: Arc<Mutex<chess::Game>>) -> T;
     fn get_next_move(
         &mut self,
         bot_color: chess::Color,
     ) -> Result<(String, bool), NoAvailableMoveError>;
+    fn provide_opponent_move(&mut self, chess_move: chess::ChessMove);
+    /// Search parameters of the game, called once right after `new`.
+    fn configure(&mut self, _config: &SearchConfig) {}
+    /// Remaining clock times before the next `get_next_move`, `None` in untimed games.
+    fn set_clock(&mut self, _clock: Option<GameClock>) {}
+    /// Flag ending the running and later searches once raised, e.g. to shut down.
+    fn set_stop_flag(&mut self, _stop_flag: Arc<AtomicBool>) {}
+    /// Whether a draw offered by the opponent is accepted.
+    fn accepts_draw(&self) -> bool {
+        false
+    }
+    /// Whether the bot resigns instead of playing the move of the last `get_next_move`.
+    fn resigns(&self) -> bool {
+        false
+    }
+    /// Statistics of the search of the last `get_next_move`, if any.
+    fn last_search_info(&self) -> Option<SearchInfo> {
+        None
+    }
+    /// Search history deciding about draws and resigning, carried over when the engine of a
+    /// game is rebuilt.
+    fn adjudicator(&self) -> Adjudicator {
+        Adjudicator::default()
+    }
+    fn set_adjudicator(&mut self, _adjudicator: Adjudicator) {}
 }
 
 pub struct Searcher {
     tree: super::tree::Tree,
-    game: Rc<RefCell<chess::Game>>,
+    game: Arc<Mutex<chess::Game>>,
+    time_manager: TimeManager,
+    // Search time of games without a clock
+    max_search_time: f32,
+    clock: Option<GameClock>,
+    draw_policy: DrawPolicy,
+    resign_policy: ResignPolicy,
+    adjudicator: Adjudicator,
 }
 
 impl Engine<Searcher> for Searcher {
-    fn new(game: Rc<RefCell<chess::Game>>) -> Self {
-        const MAX_SEARCH_DEPTH: usize = 15;
-        let current_board = (*game).borrow().current_position();
+    fn new(game: Arc<Mutex<chess::Game>>) -> Self {
+        let current_board = game.lock().unwrap().current_position();
         let tree = crate::mcts::tree::Tree::new(current_board, MAX_SEARCH_DEPTH);
-        Searcher { tree, game }
+        Searcher {
+            tree,
+            game,
+            time_manager: TimeManager::default(),
+            max_search_time: DEFAULT_MAX_SEARCH_TIME,
+            clock: None,
+            draw_policy: DrawPolicy::default(),
+            resign_policy: ResignPolicy::default(),
+            adjudicator: Adjudicator::default(),
+        }
     }
 
     fn get_next_move(
@@ -35,22 +80,66 @@ impl Engine<Searcher> for Searcher {
     ) -> Result<(String, bool), NoAvailableMoveError> {
         self._get_next_move_mcts(bot_color)
     }
+
+    fn provide_opponent_move(&mut self, chess_move: chess::ChessMove) {
+        // Make the move within the game's board
+        self.game.lock().unwrap().make_move(chess_move);
+        // Propagate move to tree
+        self.tree.provide_opponent_move(chess_move);
+    }
+
+    fn configure(&mut self, config: &SearchConfig) {
+        config.apply(&mut self.tree);
+        self.time_manager = config.time_manager.clone();
+        self.max_search_time = config.max_search_time;
+        self.draw_policy = config.draw.clone();
+        self.resign_policy = config.resign.clone();
+    }
+
+    fn set_clock(&mut self, clock: Option<GameClock>) {
+        self.clock = clock;
+    }
+
+    fn set_stop_flag(&mut self, stop_flag: Arc<AtomicBool>) {
+        self.tree.set_stop_flag(stop_flag);
+    }
+
+    fn accepts_draw(&self) -> bool {
+        self.adjudicator.accepts_draw(&self.draw_policy)
+    }
+
+    fn resigns(&self) -> bool {
+        self.adjudicator.resigns(&self.resign_policy)
+    }
+
+    fn last_search_info(&self) -> Option<SearchInfo> {
+        self.tree.last_search_info().cloned()
+    }
+
+    fn adjudicator(&self) -> Adjudicator {
+        self.adjudicator.clone()
+    }
+
+    fn set_adjudicator(&mut self, adjudicator: Adjudicator) {
+        self.adjudicator = adjudicator;
+    }
 }
 
 impl Searcher {
     // #[test]
     fn _get_next_move_random(&mut self) -> Result<(String, bool), NoAvailableMoveError> {
-        let curr_pos = (*self.game).borrow().current_position();
+        let curr_pos = self.game.lock().unwrap().current_position();
         let moves_iterator = chess::MoveGen::new_legal(&curr_pos);
-        let moves: Vec<chess::ChessMove> = moves_iterator.enumerate().map(|(_, x)| x).collect();
+        let moves: Vec<chess::ChessMove> = moves_iterator.collect();
         let str_move = match moves.choose(&mut rand::thread_rng()) {
             Some(mv) => {
                 let str_move = format!("{}", mv);
                 let offer_draw = false;
-                (*self.game)
-                    .borrow_mut()
+                self.game
+                    .lock()
+                    .unwrap()
                     .current_position()
-                    .make_move_new(mv.clone());
+                    .make_move_new(*mv);
                 Ok((str_move, offer_draw))
             }
             _ => Err(NoAvailableMoveError {}),
@@ -62,18 +151,27 @@ impl Searcher {
         &mut self,
         my_color: chess::Color,
     ) -> Result<(String, bool), NoAvailableMoveError> {
-        let best_move = self.tree.yield_best_move(my_color);
-        (*self.game).borrow_mut().make_move(best_move);
-        let offer_draw = false;
+        let search_time = match &self.clock {
+            Some(clock) => self.time_manager.move_time(clock, my_color),
+            None => self.max_search_time,
+        };
+        debug!("Searching {search_time:.2}s with clock {:?}", self.clock);
+        self.tree.set_max_search_time(search_time);
+        let best_move = self
+            .tree
+            .yield_best_move(my_color)
+            .ok_or(NoAvailableMoveError {})?;
+        self.game.lock().unwrap().make_move(best_move);
+        if let Some(info) = self.tree.last_search_info() {
+            debug!(
+                "Searched {} iterations on top of {} carried visits",
+                info.iterations, info.carried_visits
+            );
+            self.adjudicator.record(info.win_ratio);
+        }
+        let offer_draw = self.adjudicator.offers_draw(&self.draw_policy);
         Ok((best_move.to_string(), offer_draw))
     }
-
-    pub fn provide_opponent_move(&mut self, chess_move: chess::ChessMove) {
-        // Make the move within the game's board
-        (*self.game).borrow_mut().make_move(chess_move);
-        // Propagate move to tree
-        self.tree.provide_opponent_move(chess_move);
-    }
 }
 
 #[derive(Debug)]
@@ -86,3 +184,62 @@ impl fmt::Display for NoAvailableMoveError {
 }
 
 impl std::error::Error for NoAvailableMoveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn configured_searcher(max_search_time: f32) -> Searcher {
+        let game = Arc::new(Mutex::new(chess::Game::new()));
+        let mut searcher = Searcher::new(game);
+        searcher.configure(&SearchConfig {
+            max_search_time,
+            early_stop: false,
+            ..SearchConfig::default()
+        });
+        searcher
+    }
+
+    fn search_time(searcher: &mut Searcher) -> f32 {
+        searcher.get_next_move(chess::Color::White).unwrap();
+        searcher.last_search_info().unwrap().elapsed
+    }
+
+    #[test]
+    fn untimed_games_search_for_max_search_time() {
+        let mut searcher = configured_searcher(0.3);
+        let elapsed = search_time(&mut searcher);
+        assert!((0.3..1.3).contains(&elapsed), "searched {elapsed}s");
+    }
+
+    #[test]
+    fn clocks_override_max_search_time() {
+        let mut searcher = configured_searcher(5_f32);
+        // (3.3 s - 300 ms overhead) / 60 moves in emergency mode
+        searcher.set_clock(Some(GameClock {
+            wtime: 3_300,
+            btime: 3_300,
+            winc: 0,
+            binc: 0,
+        }));
+        let elapsed = search_time(&mut searcher);
+        assert!(elapsed < 1_f32, "searched {elapsed}s");
+    }
+
+    #[test]
+    fn game_over_positions_yield_no_move() {
+        let board = chess::Board::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let game = Arc::new(Mutex::new(chess::Game::new_with_board(board)));
+        let mut searcher = Searcher::new(game);
+        assert!(searcher.get_next_move(chess::Color::Black).is_err());
+    }
+
+    #[test]
+    fn raised_stop_flag_ends_the_search() {
+        let mut searcher = configured_searcher(60_f32);
+        searcher.set_stop_flag(Arc::new(AtomicBool::new(true)));
+        let elapsed = search_time(&mut searcher);
+        assert!(elapsed < 1_f32, "searched {elapsed}s");
+    }
+}
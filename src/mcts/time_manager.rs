@@ -0,0 +1,146 @@
+use lichess_api::model::board::stream::game::GameState;
+use serde::{Deserialize, Serialize};
+
+/// Remaining time and increment of both players, in milliseconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GameClock {
+    pub wtime: u64,
+    pub btime: u64,
+    pub winc: u64,
+    pub binc: u64,
+}
+
+impl GameClock {
+    fn time_and_increment(&self, color: chess::Color) -> (u64, u64) {
+        match color {
+            chess::Color::White => (self.wtime, self.winc),
+            chess::Color::Black => (self.btime, self.binc),
+        }
+    }
+}
+
+impl From<&GameState> for GameClock {
+    fn from(game_state: &GameState) -> GameClock {
+        GameClock {
+            wtime: game_state.wtime,
+            btime: game_state.btime,
+            winc: game_state.winc,
+            binc: game_state.binc,
+        }
+    }
+}
+
+/// Splits the remaining clock time into per-move search budgets. Games without a clock search
+/// for `max_search_time` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeManager {
+    /// Time lost per move to network latency, in milliseconds
+    pub move_overhead: u64,
+    /// Assumed number of moves left in the game
+    pub moves_to_go: u64,
+    /// Remaining time below which the budget shrinks to keep a reserve, in milliseconds
+    pub emergency_time: u64,
+    /// Upper bound of every budget, in seconds
+    pub max_move_time: f32,
+}
+
+impl Default for TimeManager {
+    fn default() -> TimeManager {
+        TimeManager {
+            move_overhead: 300,
+            moves_to_go: 30,
+            emergency_time: 10_000,
+            max_move_time: 60_f32,
+        }
+    }
+}
+
+impl TimeManager {
+    /// Search time in seconds for the next move of `side_to_move`.
+    pub fn move_time(&self, clock: &GameClock, side_to_move: chess::Color) -> f32 {
+        let (time, increment) = clock.time_and_increment(side_to_move);
+        // Time left once the transmission of the move is accounted for
+        let available = time.saturating_sub(self.move_overhead);
+        let moves_to_go = self.moves_to_go.max(1);
+        let budget = if time < self.emergency_time {
+            // Emergency mode: spread the remaining time over twice as many moves and rely on
+            // the increment, never spend more than a quarter of the clock
+            (available / (2 * moves_to_go) + increment / 2).min(available / 4)
+        } else {
+            // Never spend more than half of the remaining time on a single move
+            (available / moves_to_go + increment * 3 / 4).min(available / 2)
+        };
+        (budget as f32 / 1000_f32).min(self.max_move_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::Color;
+
+    fn game_clock(wtime: u64, btime: u64, winc: u64, binc: u64) -> GameClock {
+        GameClock {
+            wtime,
+            btime,
+            winc,
+            binc,
+        }
+    }
+
+    #[test]
+    fn splits_the_side_to_moves_clock() {
+        let time_manager = TimeManager::default();
+        // (300 s - 300 ms overhead) / 30 moves
+        let clock = game_clock(300_000, 60_000, 0, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 9.99);
+        assert_eq!(time_manager.move_time(&clock, Color::Black), 1.99);
+    }
+
+    #[test]
+    fn adds_most_of_the_increment() {
+        let time_manager = TimeManager::default();
+        let clock = game_clock(300_000, 300_000, 2_000, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 11.49);
+        assert_eq!(time_manager.move_time(&clock, Color::Black), 9.99);
+    }
+
+    #[test]
+    fn fewer_moves_to_go_raise_the_budget() {
+        let time_manager = TimeManager {
+            moves_to_go: 10,
+            ..TimeManager::default()
+        };
+        let clock = game_clock(300_000, 300_000, 0, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 29.97);
+        // A single move to go still keeps half of the clock
+        let time_manager = TimeManager {
+            moves_to_go: 1,
+            ..TimeManager::default()
+        };
+        let clock = game_clock(20_000, 20_000, 0, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 9.85);
+    }
+
+    #[test]
+    fn low_time_keeps_a_reserve() {
+        let time_manager = TimeManager::default();
+        // (8 s - 300 ms) / 60 moves + half of the increment
+        let clock = game_clock(8_000, 8_000, 2_000, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 1.128);
+        // At most a quarter of the clock, however large the increment
+        let clock = game_clock(8_000, 8_000, 60_000, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 1.925);
+        // A clock below the move overhead leaves nothing to search
+        let clock = game_clock(200, 200, 0, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 0_f32);
+    }
+
+    #[test]
+    fn budgets_are_capped() {
+        let time_manager = TimeManager::default();
+        let clock = game_clock(36_000_000, 36_000_000, 0, 0);
+        assert_eq!(time_manager.move_time(&clock, Color::White), 60_f32);
+    }
+}
@@ -1,481 +1,1549 @@
-use lichess_api::api::board;
 use rand::prelude::SliceRandom;
-use rand::{thread_rng, Rng};
-use std::borrow::{Borrow, BorrowMut};
-use std::cell::RefCell;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
 use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, Hasher};
-use std::rc::{Rc, Weak};
-use std::thread::current;
-use std::time::Instant;
-use tokio::select;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use crate::utils::rc_wrapper::{HashableRcRefCell, NodeRef, WNodeRef};
+use crate::mcts::outcome::GameOutcome;
+use crate::mcts::policy::{PolicyProvider, UniformPolicy};
+use crate::mcts::rave::RaveSchedule;
 use log::debug;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
+/// Search time per move when no time manager is used, in seconds.
+pub const DEFAULT_MAX_SEARCH_TIME: f32 = 5_f32;
+/// Number of plies after which a playout is scored by the static evaluation.
+pub const DEFAULT_N_CUTOFF_MOVES: usize = 200;
+/// Exploration constant of the UCT selection policy.
+pub const DEFAULT_UCT_CONSTANT: f32 = std::f32::consts::SQRT_2;
+/// Exploration constant of the PUCT (AlphaZero) selection policy.
+pub const DEFAULT_PUCT_CONSTANT: f32 = 1.5;
+/// Number of losses temporarily added to each node on a path being searched by a worker thread.
+pub const DEFAULT_VIRTUAL_LOSS: usize = 1;
+// Share of the search time `MoveSelection::RobustMax` may search on, and the rounds it is split in
+const ROBUST_MAX_EXTENSION: f32 = 0.25;
+const ROBUST_MAX_ROUNDS: usize = 5;
+// Encodings of `Node::proof`
+const PROOF_NONE: u8 = 0;
+const PROOF_WIN: u8 = 1;
+const PROOF_LOSS: u8 = 2;
+// Visits of a proven head, relative to its visits when the proof was found, after which no
+// quicker mate is looked for
+const PROOF_SEARCH_FACTOR: usize = 2;
+
+#[derive(Clone)]
 struct TreeParams {
     max_search_depth: usize,
     n_cutoff_moves: usize,
     max_search_time: f32,
+    selection_policy: SelectionPolicy,
+    simulation_policy: SimulationPolicy,
+    c_uct: f32,
+    c_puct: f32,
+    n_threads: usize,
+    parallel_mode: ParallelMode,
+    virtual_loss: usize,
+    early_stop: bool,
+    move_selection: MoveSelection,
+    max_iterations: Option<usize>,
+    rave: Option<RaveSchedule>,
+}
+
+impl TreeParams {
+    fn exploration_constant(&self) -> f32 {
+        match self.selection_policy {
+            SelectionPolicy::UCT => self.c_uct,
+            SelectionPolicy::AlphaZero => self.c_puct,
+        }
+    }
+}
+
+pub type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+
+/// Snapshot of a running search, handed to the info callback.
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub iterations: usize,
+    pub elapsed: f32,
+    pub win_ratio: f32,
+    pub principal_variation: Vec<chess::ChessMove>,
+    /// Visits of the head carried over from the searches of the former moves
+    pub carried_visits: usize,
+    /// Moves until the mate of a proven head, negative when the side to move gets mated
+    pub mate: Option<i32>,
+}
+
+/// Index of a node within the tree's arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Nodes of the tree, shared between worker threads during a parallel search.
+///
+/// Node statistics are atomic, so selection and backpropagation only need shared access.
+/// Adding nodes requires exclusive access.
+#[derive(Default)]
+struct Arena {
+    // All tree nodes, indexed by `NodeId`
+    nodes: Vec<Node>,
+    // Node of each board hash, merges transpositions into a DAG
+    node_ids: HashMap<u64, NodeId>,
+}
+
+impl Arena {
+    fn node(&self, node_id: NodeId) -> &Node {
+        &self.nodes[node_id.0]
+    }
+
+    fn node_mut(&mut self, node_id: NodeId) -> &mut Node {
+        &mut self.nodes[node_id.0]
+    }
+
+    fn add_node(&mut self, node: Node) -> NodeId {
+        let node_id = NodeId(self.nodes.len());
+        self.node_ids.insert(node.board.get_hash(), node_id);
+        self.nodes.push(node);
+        node_id
+    }
+
+    fn parent_visits(&self, node_id: NodeId) -> usize {
+        // Gather sum of all parent visits
+        let parent_visits: usize = self
+            .node(node_id)
+            .parents
+            .iter()
+            .map(|x| self.node(*x).visits())
+            .sum();
+        // Case where one of multiple parents (n_parents > 1) is yet unexplored
+        parent_visits.max(1)
+    }
+
+    fn node_score(&self, node_id: NodeId, params: &TreeParams) -> f32 {
+        // AMAF statistics are held by the edges, those from the first parent are used
+        let amaf = self.node(node_id).parents.first().and_then(|x| {
+            let parent = self.node(*x);
+            let child_index = parent.children.iter().position(|y| *y == node_id)?;
+            parent.amaf.get(child_index)
+        });
+        self.edge_score(node_id, amaf, params)
+    }
+
+    // Score of a node reached along an edge holding the given AMAF statistics
+    fn edge_score(&self, node_id: NodeId, amaf: Option<&AmafStats>, params: &TreeParams) -> f32 {
+        self.node(node_id).get_score(
+            self.parent_visits(node_id),
+            params.selection_policy,
+            params.exploration_constant(),
+            params.rave.zip(amaf),
+        )
+    }
+
+    fn principal_variation(&self, head: NodeId, max_length: usize) -> Vec<chess::ChessMove> {
+        let mut principal_variation = Vec::<chess::ChessMove>::new();
+        let mut node_id = head;
+        loop {
+            // Follow the child played once the node is proven, the most visited one otherwise
+            let best_child = self.solved_child(node_id).or_else(|| {
+                self.node(node_id)
+                    .children
+                    .iter()
+                    .filter(|x| self.node(**x).visits() > 0)
+                    .max_by_key(|x| self.node(**x).visits())
+                    .copied()
+            });
+            match best_child {
+                Some(child) if principal_variation.len() < max_length => {
+                    principal_variation.push(self.get_move_diff(node_id, child));
+                    node_id = child;
+                }
+                _ => break,
+            }
+        }
+        principal_variation
+    }
+
+    fn search_info(
+        &self,
+        head: NodeId,
+        params: &TreeParams,
+        iterations: usize,
+        elapsed: f32,
+        carried_visits: usize,
+    ) -> SearchInfo {
+        let principal_variation = self.principal_variation(head, params.max_search_depth);
+        // Win ratio of the first move of the principal variation, from the side to move's view,
+        // or the result of a proven head
+        let win_ratio = match self.node(head).proof() {
+            Some(Proof::Win) => 0_f32,
+            Some(Proof::Loss) => 1_f32,
+            None => self
+                .node(head)
+                .children
+                .iter()
+                .map(|x| self.node(*x))
+                .filter(|x| x.visits() > 0)
+                .max_by_key(|x| x.visits())
+                .map(|x| x.get_win_ratio())
+                .unwrap_or(0.5),
+        };
+        // A proven loss of the head mates within its plies, a proven win gets mated
+        let plies = self.node(head).proof_plies() as i32;
+        let mate = match self.node(head).proof() {
+            Some(Proof::Loss) => Some((plies + 1) / 2),
+            Some(Proof::Win) => Some(-plies / 2),
+            None => None,
+        };
+        SearchInfo {
+            iterations,
+            elapsed,
+            win_ratio,
+            principal_variation,
+            carried_visits,
+            mate,
+        }
+    }
+
+    /// Visited child of `head` to play under `move_selection`, ties are broken by visits.
+    /// Children proven to lose are left out.
+    ///
+    /// `MoveSelection::RobustMax` only returns a child which is both the most visited and the
+    /// best valued one.
+    fn best_child(&self, head: NodeId, move_selection: MoveSelection) -> Option<NodeId> {
+        let by_visits = |x: &Node, y: &Node| x.visits().cmp(&y.visits());
+        let by_value = |x: &Node, y: &Node| {
+            x.get_win_ratio()
+                .total_cmp(&y.get_win_ratio())
+                .then(by_visits(x, y))
+        };
+        let best_by = |compare: &dyn Fn(&Node, &Node) -> std::cmp::Ordering| {
+            self.node(head)
+                .children
+                .iter()
+                .copied()
+                .filter(|x| {
+                    self.node(*x).visits() > 0 && self.node(*x).proof() != Some(Proof::Loss)
+                })
+                .max_by(|x, y| compare(self.node(*x), self.node(*y)))
+        };
+        match move_selection {
+            MoveSelection::MaxVisits => best_by(&|x, y| by_visits(x, y).then(by_value(x, y))),
+            MoveSelection::MaxValue => best_by(&by_value),
+            MoveSelection::RobustMax => {
+                let robust_child = best_by(&|x, y| by_visits(x, y).then(by_value(x, y)));
+                robust_child.filter(|x| best_by(&by_value) == Some(*x))
+            }
+            MoveSelection::LowerConfidenceBound(c) => {
+                let lower_bound = |x: &Node| x.get_win_ratio() - c / (x.visits() as f32).sqrt();
+                best_by(&|x, y| {
+                    lower_bound(x)
+                        .total_cmp(&lower_bound(y))
+                        .then(by_visits(x, y))
+                })
+            }
+        }
+    }
+
+    /// Child of a proven `head` to play: the quickest proven win, or the most visited move of a
+    /// lost position. `None` while the head is not proven.
+    fn solved_child(&self, head: NodeId) -> Option<NodeId> {
+        let children = &self.node(head).children;
+        match self.node(head).proof()? {
+            Proof::Loss => children
+                .iter()
+                .copied()
+                .filter(|x| self.node(*x).proof() == Some(Proof::Win))
+                .min_by_key(|x| self.node(*x).proof_plies()),
+            Proof::Win => children
+                .iter()
+                .copied()
+                .max_by_key(|x| self.node(*x).visits()),
+        }
+    }
+
+    /// Proves `node_id` if its game is over and carries the proofs up to its ancestors.
+    ///
+    /// A node is a proven loss for the player who moved into it once one of its children is a
+    /// proven win, and a proven win once all of its children are proven losses. Proofs hold for
+    /// every parent of a transposition.
+    ///
+    /// Proven nodes also count the plies until the mate: the quickest proven win of the side to
+    /// move, or its slowest loss. Ancestors are updated whenever a quicker line is proven.
+    fn solve(&self, node_id: NodeId) {
+        let node = self.node(node_id);
+        if node.proof().is_some() || node.board.status() != chess::BoardStatus::Checkmate {
+            return;
+        }
+        node.set_proof(Proof::Win, 0);
+        let mut pending_nodes = node.parents.clone();
+        while let Some(node_id) = pending_nodes.pop() {
+            let node = self.node(node_id);
+            let children = node.children.iter().map(|x| self.node(*x));
+            let quickest_win = children
+                .clone()
+                .filter(|x| x.proof() == Some(Proof::Win))
+                .map(|x| x.proof_plies())
+                .min();
+            let (proof, plies) = match quickest_win {
+                Some(plies) => (Proof::Loss, plies + 1),
+                None if children.clone().all(|x| x.proof() == Some(Proof::Loss)) => {
+                    let slowest_loss = children.map(|x| x.proof_plies()).max().unwrap_or(0);
+                    (Proof::Win, slowest_loss + 1)
+                }
+                None => continue,
+            };
+            // Plies only decrease, which ends the updates on cycles of the DAG
+            if node.proof() == Some(proof) && node.proof_plies() <= plies {
+                continue;
+            }
+            node.set_proof(proof, plies);
+            pending_nodes.extend(node.parents.iter().copied());
+        }
+    }
+
+    /// Whether a proven `head` is done searching: the game is lost anyway, mated at once, all of
+    /// the moves are proven, or no quicker mate was proven in `PROOF_SEARCH_FACTOR` times the
+    /// visits it took to prove the current one.
+    fn proof_settled(&self, head: NodeId) -> bool {
+        let node = self.node(head);
+        match node.proof() {
+            None => false,
+            Some(Proof::Win) => true,
+            Some(Proof::Loss) => {
+                node.proof_plies() == 1
+                    || node.visits()
+                        >= PROOF_SEARCH_FACTOR * node.proof_visits.load(Ordering::Relaxed)
+                    || node
+                        .children
+                        .iter()
+                        .all(|x| self.node(*x).proof().is_some())
+            }
+        }
+    }
+
+    /// Whether the most visited child of `head` keeps the lead even if its closest rival got all
+    /// of the `remaining_iterations`. A single legal move is always unassailable.
+    fn best_child_unassailable(&self, head: NodeId, remaining_iterations: f32) -> bool {
+        let children = &self.node(head).children;
+        if children.len() == 1 {
+            return true;
+        }
+        // Visits of the two most visited children
+        let (first, second) = children.iter().map(|x| self.node(*x).visits()).fold(
+            (0_usize, 0_usize),
+            |(first, second), visits| {
+                if visits > first {
+                    (visits, first)
+                } else {
+                    (first, second.max(visits))
+                }
+            },
+        );
+        (first - second) as f32 > remaining_iterations
+    }
+
+    /// Descends from `root` to a leaf, returning the leaf and the path leading to it. Proven
+    /// nodes are not descended into, as searching them can not change their value.
+    ///
+    /// Each node on the path receives `virtual_loss` pending losses, to be removed once the
+    /// playout has been backpropagated.
+    fn select(
+        &self,
+        root: NodeId,
+        params: &TreeParams,
+        virtual_loss: usize,
+    ) -> (NodeId, Vec<NodeId>) {
+        let mut leaf = root;
+        // Nodes visited on the way down
+        let mut path = vec![root];
+        self.node(root).add_virtual_loss(virtual_loss);
+        // While the node has children
+        while self.node(leaf)._has_children() {
+            let selected_child = self
+                .node(leaf)
+                .children
+                .iter()
+                .zip(&self.node(leaf).amaf)
+                .filter(|(x, _)| self.node(**x).proof().is_none())
+                // Gather score of each child node
+                .map(|(x, amaf)| (*x, self.edge_score(*x, Some(amaf), params)))
+                // Gather argmax of children scores
+                .fold(
+                    None,
+                    |best: Option<(NodeId, f32)>, (child, score)| match best {
+                        Some((_, score_max)) if score <= score_max => best,
+                        _ => Some((child, score)),
+                    },
+                );
+            let selected_child = match selected_child {
+                Some((child, _)) if !path.contains(&child) => child,
+                // Stop once all children are proven or on a repeated position, the DAG may
+                // contain cycles
+                _ => break,
+            };
+            self.node(selected_child).add_virtual_loss(virtual_loss);
+            path.push(selected_child);
+            leaf = selected_child;
+        }
+        (leaf, path)
+    }
+
+    /// Credits the outcome of `playout` to the nodes of the searched `path`, from the head down
+    /// to the node played out, and to the AMAF statistics of their children under RAVE.
+    ///
+    /// Only the path leading to the playout is updated: the other parents of a transposed node
+    /// did not choose the moves leading to it in this iteration.
+    fn backpropagate(&self, path: &[NodeId], playout: &Playout, params: &TreeParams) {
+        for (i, node_id) in path.iter().enumerate() {
+            // A path closing a cycle holds its last node twice
+            if path[..i].contains(node_id) {
+                continue;
+            }
+            let node = self.node(*node_id);
+            node.add_white_wins(playout.outcome.white_score());
+            // Update visit count
+            node.visits.fetch_add(1, Ordering::Relaxed);
+        }
+        if params.rave.is_some() {
+            self.backpropagate_amaf(path, playout);
+        }
+    }
+
+    /// Credits the outcome of `playout` to the AMAF statistics of the children of each node of
+    /// `path` whose move the side to move made later on, in the tree or in the playout.
+    fn backpropagate_amaf(&self, path: &[NodeId], playout: &Playout) {
+        // Moves from each node of the path to the next, followed by the playout's
+        let moves: Vec<chess::ChessMove> = path
+            .windows(2)
+            .map(|x| self.get_move_diff(x[0], x[1]))
+            .chain(playout.moves.iter().copied())
+            .collect();
+        for (i, node_id) in path.iter().enumerate() {
+            let node = self.node(*node_id);
+            if path[..i].contains(node_id) || !node._has_children() {
+                continue;
+            }
+            // Moves of the node's side to move, every other move from the node on
+            let played_moves: HashSet<chess::ChessMove> =
+                moves[i..].iter().step_by(2).copied().collect();
+            // Edges are stored in move generation order
+            let edges = chess::MoveGen::new_legal(&node.board).zip(&node.amaf);
+            for (chess_move, amaf) in edges {
+                if played_moves.contains(&chess_move) {
+                    amaf.add_white_wins(playout.outcome.white_score());
+                }
+            }
+        }
+    }
+
+    fn add_children(&mut self, parent: NodeId, boards: impl Iterator<Item = (chess::Board, f32)>) {
+        let depth = self.node(parent).depth + 1;
+        // Allocate a vector of all nodes for the given boards, their legal moves are counted
+        // up front so the size hint is exact
+        let mut child_nodes = Vec::<NodeId>::with_capacity(boards.size_hint().0);
+        for (board, prior) in boards {
+            // Check if the board is already tracked in the tree
+            let child = match self.node_ids.get(&board.get_hash()) {
+                // If so, append parent to the existing node's parents
+                Some(node_id) => {
+                    let node_id = *node_id;
+                    let node = self.node_mut(node_id);
+                    if !node.parents.contains(&parent) {
+                        node.parents.push(parent);
+                    }
+                    node_id
+                }
+                // Otherwise create the node, transposed nodes keep the prior of their first parent
+                None => {
+                    let mut node = Node::new(board, depth);
+                    node.parents.push(parent);
+                    node.prior = prior;
+                    self.add_node(node)
+                }
+            };
+            child_nodes.push(child);
+        }
+        // Assign children vector to parent, along with the AMAF statistics of its edges
+        let parent_node = self.node_mut(parent);
+        parent_node.amaf = child_nodes.iter().map(|_| AmafStats::default()).collect();
+        parent_node.children = child_nodes;
+    }
+
+    /// Drops all nodes unreachable from `new_head`, which becomes the node `NodeId(0)`.
+    fn retain_reachable_nodes(&mut self, new_head: NodeId) {
+        // Assign new ids to the nodes reachable from the new head, breadth first
+        let mut new_ids: Vec<Option<NodeId>> = vec![None; self.nodes.len()];
+        let mut reachable_nodes = vec![new_head];
+        new_ids[new_head.0] = Some(NodeId(0));
+        let mut i = 0;
+        while i < reachable_nodes.len() {
+            for child in &self.nodes[reachable_nodes[i].0].children {
+                if new_ids[child.0].is_none() {
+                    new_ids[child.0] = Some(NodeId(reachable_nodes.len()));
+                    reachable_nodes.push(*child);
+                }
+            }
+            i += 1;
+        }
+        // Move the reachable nodes into a new arena, dropping all others at once
+        let mut old_nodes: Vec<Option<Node>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.nodes = reachable_nodes
+            .iter()
+            .map(|x| {
+                let mut node = old_nodes[x.0].take().unwrap();
+                node.children = node
+                    .children
+                    .iter()
+                    .map(|y| new_ids[y.0].unwrap())
+                    .collect();
+                node.parents = node.parents.iter().filter_map(|y| new_ids[y.0]).collect();
+                node
+            })
+            .collect();
+        // Rebuild the transposition lookup
+        self.node_ids = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.board.get_hash(), NodeId(i)))
+            .collect();
+        // Clear the heads parents
+        self.node_mut(NodeId(0)).parents.clear();
+    }
+
+    /// Adds the statistics of `other_head` and its children to the head `NodeId(0)` and its
+    /// children, copying the head and its moves over first where they are missing. Proofs keep
+    /// their quickest mate.
+    fn add_root_statistics(&mut self, other: &Arena, other_head: NodeId) {
+        let other_head_node = other.node(other_head);
+        if self.nodes.is_empty() {
+            self.add_node(Node::new(other_head_node.board, other_head_node.depth));
+        }
+        let head = NodeId(0);
+        // Expand the head if only the other tree did
+        if !self.node(head)._has_children() && other_head_node._has_children() {
+            let children = other_head_node
+                .children
+                .iter()
+                .map(|x| (other.node(*x).board, other.node(*x).prior));
+            self.add_children(head, children);
+        }
+        // Both heads list their children in move generation order
+        let nodes = std::iter::once(head).chain(self.node(head).children.iter().copied());
+        let other_nodes =
+            std::iter::once(other_head).chain(other_head_node.children.iter().copied());
+        for (node_id, other_node_id) in nodes.zip(other_nodes) {
+            let (node, other_node) = (self.node(node_id), other.node(other_node_id));
+            node.visits
+                .fetch_add(other_node.visits(), Ordering::Relaxed);
+            node.add_white_wins(other_node.white_wins());
+            if let Some(proof) = other_node.proof() {
+                if node.proof().is_none() || other_node.proof_plies() < node.proof_plies() {
+                    node.set_proof(proof, other_node.proof_plies());
+                }
+            }
+        }
+    }
+
+    fn get_move_diff(&self, parent: NodeId, child: NodeId) -> chess::ChessMove {
+        let current_board = self.node(parent).board;
+        let mut move_generator = chess::MoveGen::new_legal(&current_board);
+        // Children are stored in move generation order
+        let move_id = self
+            .node(parent)
+            .children
+            .iter()
+            .position(|x| *x == child)
+            .expect("Child node not found amongst its parent's children");
+        move_generator.nth(move_id).unwrap()
+    }
 }
 
 pub struct Tree {
-    nodes: HashSet<NodeRef>,
-    head: NodeRef,
+    arena: Arena,
+    head: NodeId,
     params: TreeParams,
-    rng: rand::rngs::ThreadRng,
+    rng: StdRng,
+    policy_provider: Arc<dyn PolicyProvider>,
+    stop_flag: Option<Arc<AtomicBool>>,
+    info_callback: Option<InfoCallback>,
+    // Final statistics of the last `yield_best_move`
+    last_search_info: Option<SearchInfo>,
+    // Visits the head had when it became the head
+    carried_visits: usize,
+    // Summed up root statistics of the root parallel workers searching the current move
+    worker_root: Option<Arena>,
 }
 
 impl Tree {
     pub fn new(initial_board: chess::Board, max_search_depth: usize) -> Tree {
-        // Define parameters
-        const MAX_SEARCH_TIME: f32 = 5_f32;
-        const CUTOFF_MOVES: usize = 200;
         // Instantiate tree's parameters
         let params = TreeParams {
             max_search_depth,
-            n_cutoff_moves: CUTOFF_MOVES,
-            max_search_time: MAX_SEARCH_TIME,
+            n_cutoff_moves: DEFAULT_N_CUTOFF_MOVES,
+            max_search_time: DEFAULT_MAX_SEARCH_TIME,
+            selection_policy: SelectionPolicy::default(),
+            simulation_policy: SimulationPolicy::default(),
+            c_uct: DEFAULT_UCT_CONSTANT,
+            c_puct: DEFAULT_PUCT_CONSTANT,
+            n_threads: 1,
+            parallel_mode: ParallelMode::default(),
+            virtual_loss: DEFAULT_VIRTUAL_LOSS,
+            early_stop: true,
+            move_selection: MoveSelection::default(),
+            max_iterations: None,
+            rave: None,
         };
         // Create node with starting game position
-        let head = HashableRcRefCell::new(Node::new(None, initial_board));
-        // Create hashset of all tree nodes and insert the head
-        let mut nodes = HashSet::<NodeRef>::new();
-        nodes.insert(head.clone());
+        let mut arena = Arena::default();
+        let head = arena.add_node(Node::new(initial_board, 0));
 
         // Return the tree
         Tree {
-            nodes,
+            arena,
             head,
             params,
-            rng: rand::thread_rng(),
+            rng: StdRng::from_entropy(),
+            policy_provider: Arc::new(UniformPolicy),
+            stop_flag: None,
+            info_callback: None,
+            last_search_info: None,
+            carried_visits: 0,
+            worker_root: None,
         }
     }
 
+    pub fn set_max_search_time(&mut self, max_search_time: f32) {
+        self.params.max_search_time = max_search_time;
+    }
+
+    /// Maximal depth of the tree below its head.
+    pub fn set_max_search_depth(&mut self, max_search_depth: usize) {
+        self.params.max_search_depth = max_search_depth;
+    }
+
+    pub fn set_selection_policy(&mut self, selection_policy: SelectionPolicy) {
+        self.params.selection_policy = selection_policy;
+    }
+
+    pub fn set_simulation_policy(&mut self, simulation_policy: SimulationPolicy) {
+        self.params.simulation_policy = simulation_policy;
+    }
+
+    /// Number of plies after which a playout is stopped and scored by the static evaluation.
+    pub fn set_n_cutoff_moves(&mut self, n_cutoff_moves: usize) {
+        self.params.n_cutoff_moves = n_cutoff_moves;
+    }
+
+    pub fn set_c_uct(&mut self, c_uct: f32) {
+        self.params.c_uct = c_uct;
+    }
+
+    pub fn set_c_puct(&mut self, c_puct: f32) {
+        self.params.c_puct = c_puct;
+    }
+
+    /// Number of threads searching, see `set_parallel_mode`.
+    ///
+    /// A single thread (the default) searches on the calling thread without any locking.
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        self.params.n_threads = n_threads.max(1);
+    }
+
+    /// Seeds the random number generator of single-threaded searches, which then repeat
+    /// themselves under an iteration budget without early stop.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Stops searching once the most visited move cannot be caught up within the time limit.
+    pub fn set_early_stop(&mut self, early_stop: bool) {
+        self.params.early_stop = early_stop;
+    }
+
+    /// Iterations after which a search stops, on top of the time limit. The iterations of the
+    /// calling thread only count in `ParallelMode::Root`.
+    pub fn set_max_iterations(&mut self, max_iterations: Option<usize>) {
+        self.params.max_iterations = max_iterations;
+    }
+
+    /// Blends the AMAF win ratio of each move into its win ratio while selecting, weighted by
+    /// the given schedule. Off (`None`) by default.
+    pub fn set_rave(&mut self, rave: Option<RaveSchedule>) {
+        self.params.rave = rave;
+    }
+
+    /// How the move to play is chosen once the search is over.
+    pub fn set_move_selection(&mut self, move_selection: MoveSelection) {
+        self.params.move_selection = move_selection;
+    }
+
+    /// How the search is spread over the threads set by `set_n_threads`.
+    pub fn set_parallel_mode(&mut self, parallel_mode: ParallelMode) {
+        self.params.parallel_mode = parallel_mode;
+    }
+
+    /// Losses temporarily added to the nodes a worker thread is searching through, steering the
+    /// other workers towards different lines. Only used by `ParallelMode::Tree`.
+    pub fn set_virtual_loss(&mut self, virtual_loss: usize) {
+        self.params.virtual_loss = virtual_loss;
+    }
+
+    /// Replaces the move priors used by `SelectionPolicy::AlphaZero` (uniform by default).
+    pub fn set_policy_provider(&mut self, policy_provider: Box<dyn PolicyProvider>) {
+        self.policy_provider = Arc::from(policy_provider);
+    }
+
+    /// Lets another thread interrupt `yield_best_move` before the time limit is reached.
+    pub fn set_stop_flag(&mut self, stop_flag: Arc<AtomicBool>) {
+        self.stop_flag = Some(stop_flag);
+    }
+
+    /// Registers a callback invoked about once per second while searching, and once at the end.
+    pub fn set_info_callback(&mut self, info_callback: InfoCallback) {
+        self.info_callback = Some(info_callback);
+    }
+
+    pub fn head(&self) -> NodeId {
+        self.head
+    }
+
+    pub fn head_board(&self) -> chess::Board {
+        self.node(self.head).board
+    }
+
+    pub fn node(&self, node_id: NodeId) -> &Node {
+        self.arena.node(node_id)
+    }
+
+    /// Iterates over the ids of all nodes currently held by the tree.
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.arena.nodes.len()).map(NodeId)
+    }
+
+    /// Selection score of a node under the tree's selection policy.
+    pub fn node_score(&self, node_id: NodeId) -> f32 {
+        self.arena.node_score(node_id, &self.params)
+    }
+
+    pub fn principal_variation(&self) -> Vec<chess::ChessMove> {
+        self.arena
+            .principal_variation(self.head, self.params.max_search_depth)
+    }
+
+    /// Statistics of the last search, the win ratio is seen from the side which moved.
+    pub fn last_search_info(&self) -> Option<&SearchInfo> {
+        self.last_search_info.as_ref()
+    }
+
+    fn _report_search_info(&mut self, iterations: usize, elapsed: f32) {
+        if self.info_callback.is_some() {
+            let info = self._search_info(iterations, elapsed);
+            if let Some(callback) = self.info_callback.as_mut() {
+                callback(&info);
+            }
+        }
+    }
+
+    fn _search_info(&self, iterations: usize, elapsed: f32) -> SearchInfo {
+        let merged_root = match self._merged_root() {
+            Some(merged_root) => merged_root,
+            None => {
+                return self.arena.search_info(
+                    self.head,
+                    &self.params,
+                    iterations,
+                    elapsed,
+                    self.carried_visits,
+                )
+            }
+        };
+        let mut info = merged_root.search_info(
+            NodeId(0),
+            &self.params,
+            iterations,
+            elapsed,
+            self.carried_visits,
+        );
+        // The merged root only holds the head's moves, the kept tree continues the line
+        if let Some(first_move) = info.principal_variation.first() {
+            let board = self.head_board().make_move_new(*first_move);
+            if let Some(node_id) = self.arena.node_ids.get(&board.get_hash()) {
+                info.principal_variation.extend(self.arena.principal_variation(
+                    *node_id,
+                    self.params.max_search_depth.saturating_sub(1),
+                ));
+            }
+        }
+        info
+    }
+
+    /// Head and moves of the tree with the root statistics of the root parallel workers added,
+    /// `None` unless workers searched the current move. The tree itself keeps its own statistics,
+    /// which are reused for the next moves.
+    fn _merged_root(&self) -> Option<Arena> {
+        let worker_root = self.worker_root.as_ref()?;
+        let mut merged_root = Arena::default();
+        merged_root.add_root_statistics(&self.arena, self.head);
+        merged_root.add_root_statistics(worker_root, NodeId(0));
+        Some(merged_root)
+    }
+
     pub fn provide_opponent_move(&mut self, chess_move: chess::ChessMove) {
         // Prune all unreachable nodes
         self._prune_tree_based_on_move_and_update_head(chess_move);
     }
 
-    pub fn yield_best_move(&mut self, color_to_play: chess::Color) -> chess::ChessMove {
+    /// Searches the head and plays the chosen move on the tree, `None` if the game is over.
+    pub fn yield_best_move(&mut self, color_to_play: chess::Color) -> Option<chess::ChessMove> {
+        if chess::MoveGen::new_legal(&self.head_board()).len() == 0 {
+            debug!("No legal move to search in {}", self.head_board());
+            return None;
+        }
         // Calculates the upper confidence bounds for each tree node
-        self._populate_tree();
+        let now = Instant::now();
+        self.worker_root = None;
+        let mut n_iterations = self._populate();
+        if self.params.move_selection == MoveSelection::RobustMax {
+            n_iterations += self._extend_search_for_robust_max();
+        }
+        // A search stopped before its first iteration still needs a visited move to play
+        while self.arena.solved_child(self.head).is_none()
+            && self
+                .arena
+                .best_child(self.head, MoveSelection::MaxVisits)
+                .is_none()
+        {
+            search_iteration(
+                &mut self.arena,
+                self.head,
+                &self.params,
+                &*self.policy_provider,
+                &mut self.rng,
+            );
+            n_iterations += 1;
+        }
+        debug!("Populate tree left with {n_iterations} iterations");
+        let info = self._search_info(n_iterations, now.elapsed().as_secs_f32());
+        if let Some(callback) = self.info_callback.as_mut() {
+            callback(&info);
+        }
+        self.last_search_info = Some(info);
         // Select the best move based on the current estimate
         let chess_move = self._yield_best_move(color_to_play);
         // Plot graph in critical situation
-        // crate::utils::graph_visualization::draw_graph(&self.nodes, &self.head, "Tree");
+        // crate::utils::graph_visualization::draw_graph(self, "Tree");
         // std::process::exit(0);
-        chess_move
+        Some(chess_move)
+    }
+
+    fn _populate(&mut self) -> usize {
+        match (self.params.n_threads, self.params.parallel_mode) {
+            (1, _) => self._populate_tree(),
+            (_, ParallelMode::Tree) => self._populate_tree_shared(),
+            (_, ParallelMode::Root) => self._populate_tree_root_parallel(),
+            (_, ParallelMode::Leaf) => self._populate_tree_leaf_parallel(),
+        }
+    }
+
+    /// Searches on in short rounds until the most visited move is also the best valued one,
+    /// for at most `ROBUST_MAX_EXTENSION` of the search time.
+    fn _extend_search_for_robust_max(&mut self) -> usize {
+        let params = self.params.clone();
+        let round_share = ROBUST_MAX_EXTENSION / ROBUST_MAX_ROUNDS as f32;
+        self.params.max_search_time = params.max_search_time * round_share;
+        self.params.max_iterations = params
+            .max_iterations
+            .map(|x| ((x as f32 * round_share) as usize).max(1));
+        // The most visited move keeping its lead is no reason to stop here
+        self.params.early_stop = false;
+        let mut n_iterations = 0;
+        for _ in 0..ROBUST_MAX_ROUNDS {
+            let robust_child = match self._merged_root() {
+                Some(merged_root) => merged_root.best_child(NodeId(0), MoveSelection::RobustMax),
+                None => self.arena.best_child(self.head, MoveSelection::RobustMax),
+            };
+            if robust_child.is_some()
+                || self.arena.proof_settled(self.head)
+                || self._search_finished(0., 0)
+            {
+                break;
+            }
+            n_iterations += self._populate();
+        }
+        self.params = params;
+        n_iterations
     }
 
     fn _yield_best_move(&mut self, color_to_play: chess::Color) -> chess::ChessMove {
-        // Compute argmax amongst children scores
-        let best_child_index = (*self.head)
-            .borrow()
-            .children
-            .iter()
-            .map(|x| {
-                // Compute pure win ratio
-                (**x).borrow().get_win_ratio(Some(color_to_play))
-            })
-            .enumerate()
-            .fold((0_usize, 0_f32), |(id_max, score_max), (i, score)| {
-                if score > score_max {
-                    (i, score)
-                } else {
-                    (id_max, score_max)
-                }
-            })
-            .0;
-        // Select best child
-        let best_child = (*self.head).borrow().children[best_child_index].clone();
+        debug_assert_eq!(self.head_board().side_to_move(), color_to_play);
+        // Root parallel workers take part in the decision only
+        let merged_root = self._merged_root();
+        let (arena, head) = match &merged_root {
+            Some(merged_root) => (merged_root, NodeId(0)),
+            None => (&self.arena, self.head),
+        };
+        // A proven win is played right away, a robust-max child may not exist, the most visited
+        // one is played then
+        let best_child = arena
+            .solved_child(head)
+            .or_else(|| arena.best_child(head, self.params.move_selection))
+            .or_else(|| arena.best_child(head, MoveSelection::MaxVisits))
+            .expect("The head has a visited child after the search");
         // Convert best child to chess move
-        let chess_move = Node::_get_move_diff(self.head.clone(), best_child);
+        let chess_move = arena.get_move_diff(head, best_child);
         // Remove all but the selected children (and their now unreachable children) from the tree
+        self.worker_root = None;
         self._prune_tree_based_on_move_and_update_head(chess_move);
         chess_move
     }
 
     fn _prune_tree_based_on_move_and_update_head(&mut self, chess_move: chess::ChessMove) {
         // Define the chosen child node
-        let board = (*self.head).borrow().board.clone();
-        let mut target_board = chess::Board::default();
-        board.make_move(chess_move, &mut target_board);
-        // Chosen child node
-        let best_child = HashableRcRefCell::new(Node::new(Some(self.head.clone()), target_board));
-        // Drop all but the chosen child nodes
-        (*self.head)
-            .borrow()
-            .children
-            .iter()
-            .filter(|x| **x != best_child)
-            .for_each(|x| Self::drop_node(&mut self.nodes, &x));
-        // Drop head from nodes
-        self.nodes.remove(&self.head);
-        // Update the tree's head
-        self.head = best_child;
-        // Clear the heads parents
-        (*self.head).borrow_mut().parents.clear();
+        let head = self.node(self.head);
+        let target_board = head.board.make_move_new(chess_move);
+        let target_depth = head.depth + 1;
+        // Chosen child node, created if the head was never expanded
+        let best_child = match self.arena.node_ids.get(&target_board.get_hash()) {
+            Some(node_id) => *node_id,
+            None => self.arena.add_node(Node::new(target_board, target_depth)),
+        };
+        let former_visits = self.node(self.head).visits();
+        // Drop all nodes unreachable from the chosen child and make it the tree's head, its
+        // subtree and statistics are searched on
+        self.arena.retain_reachable_nodes(best_child);
+        self.head = NodeId(0);
+        self.carried_visits = self.node(self.head).visits();
+        debug!(
+            "Head moved by {chess_move}: {} of {former_visits} visits and {} nodes carried over",
+            self.carried_visits,
+            self.arena.nodes.len()
+        );
+    }
+
+    /// Visits the head kept from the searches of the former moves.
+    pub fn carried_visits(&self) -> usize {
+        self.carried_visits
     }
 
-    fn _populate_tree(&mut self) {
+    fn _populate_tree(&mut self) -> usize {
         debug!("Populate tree called");
         let now = Instant::now();
-        let starting_node = self.head.clone();
+        let starting_node = self.head;
         let mut n_iterations: usize = 0;
+        let mut last_report: f32 = 0.;
         loop {
-            let selected_node = self.select(starting_node.clone(), SelectionPolicy::UCT);
-            let expanded_node = self.expand(&selected_node);
-            let result = self.simulate(&expanded_node, SimulationPolicy::Random);
-            self.backpropagate(&expanded_node.downgrade(), result);
-            // Time limit
+            search_iteration(
+                &mut self.arena,
+                starting_node,
+                &self.params,
+                &*self.policy_provider,
+                &mut self.rng,
+            );
             n_iterations += 1;
-            if now.elapsed().as_secs_f32() > self.params.max_search_time {
+            // Time limit and external stop request
+            let elapsed = now.elapsed().as_secs_f32();
+            if self._search_finished(elapsed, n_iterations)
+                || self._best_move_decided(&self.arena, n_iterations, elapsed)
+            {
                 break;
             }
+            // Periodic search report
+            if elapsed - last_report >= 1. {
+                last_report = elapsed;
+                self._report_search_info(n_iterations, elapsed);
+            }
             // Tree already fully explored limit
             // @TODO
         }
-        debug!("Populate tree left with {n_iterations} iterations");
         // Plot graph in critical situation
-        // if n_iterations > 10000 { crate::utils::graph_visualization::draw_graph(&self.nodes, &self.head, "Tree");}
+        // if n_iterations > 10000 { crate::utils::graph_visualization::draw_graph(self, "Tree");}
+        n_iterations
     }
 
-    fn select(&mut self, root: NodeRef, selection_policy: SelectionPolicy) -> NodeRef {
-        let leaf: NodeRef;
-        // If root node has children
-        let has_children = (*root).borrow()._has_children();
-        let is_not_terminal = (*root).borrow()._is_not_terminal();
-        if has_children {
-            let selected_child_id = (*root)
-                .borrow()
-                .children
-                .iter()
-                // Gather score of each child node
-                .map(|child_node| (**child_node).borrow().score)
-                .enumerate()
-                // Gather argmax of children scores
-                .fold((0_usize, 0_f32), |(id_max, score_max), (i, score)| {
-                    if score > score_max {
-                        (i, score)
-                    } else {
-                        (id_max, score_max)
-                    }
-                })
-                .0;
-            let selected_child = (*root).borrow().children[selected_child_id].clone();
-            leaf = self.select(selected_child, selection_policy);
-        }
-        // If root node has no children and is not terminal
-        else if is_not_terminal {
-            leaf = root;
-        }
-        // If root node has no children and is terminal
-        else {
-            leaf = root;
-        }
-        leaf.clone()
-    }
-
-    fn expand(&mut self, root: &NodeRef) -> NodeRef {
-        // Asserts root hasno children
-        assert!(!(**root).borrow()._has_children());
-        // If target depth not yet reached
-        if (**root).borrow().depth < self.params.max_search_depth + (*self.head).borrow().depth {
-            // Get root board
-            let current_board = (**root).borrow().board;
-            // Get root legal moves
-            let move_generator = chess::MoveGen::new_legal(&current_board);
-            // let available_moves: Vec<chess::ChessMove> = move_generator.collect();
-            let boards = move_generator.map(|x| {
-                let mut target_board = chess::Board::default();
-                current_board.make_move(x, &mut target_board);
-                target_board
-            });
-            // Add each legal move to the tree
-            self.add_children(root, boards);
-        }
-        // If expansion created new children
-        if (**root).borrow()._has_children() {
-            (**root)
-                .borrow()
-                .children
-                .choose(&mut self.rng)
-                .unwrap()
-                .clone()
-        }
-        // Target depth reached or terminal board state reached
-        else {
-            root.clone()
-        }
-    }
-
-    fn simulate(
-        &mut self,
-        root: &NodeRef,
-        simulation_policy: SimulationPolicy,
-    ) -> (chess::BoardStatus, chess::Color) {
-        if let SimulationPolicy::Random = simulation_policy {
-            // Tree leaf's board
-            let mut board = (**root).borrow().board.clone();
-            let mut target_board = chess::Board::default();
-            for _ in 0..self.params.n_cutoff_moves {
-                let mut move_generator = chess::MoveGen::new_legal(&board);
-                let n_moves = move_generator.len();
-                if n_moves > 0 {
-                    let move_index = self.rng.gen_range(0, n_moves);
-                    board.make_move(move_generator.nth(move_index).unwrap(), &mut target_board);
-                    std::mem::swap(&mut board, &mut target_board);
-                } else {
+    /// Searches with `n_threads` workers sharing the tree, while the calling thread keeps
+    /// track of the time limit and reports the search progress.
+    fn _populate_tree_shared(&mut self) -> usize {
+        debug!(
+            "Populate tree called with {} worker threads",
+            self.params.n_threads
+        );
+        let now = Instant::now();
+        let arena = RwLock::new(std::mem::take(&mut self.arena));
+        let workers_stop_flag = AtomicBool::new(false);
+        let n_iterations = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..self.params.n_threads {
+                scope.spawn(|| {
+                    search_worker(
+                        &arena,
+                        self.head,
+                        &self.params,
+                        &*self.policy_provider,
+                        &workers_stop_flag,
+                        &n_iterations,
+                    )
+                });
+            }
+            let mut last_report: f32 = 0.;
+            loop {
+                std::thread::sleep(Duration::from_millis(10));
+                let elapsed = now.elapsed().as_secs_f32();
+                let n_iterations = n_iterations.load(Ordering::Relaxed);
+                let best_move_decided =
+                    self._best_move_decided(&arena.read().unwrap(), n_iterations, elapsed);
+                if self._search_finished(elapsed, n_iterations) || best_move_decided {
+                    workers_stop_flag.store(true, Ordering::Relaxed);
                     break;
                 }
+                // Periodic search report
+                if elapsed - last_report >= 1. {
+                    last_report = elapsed;
+                    if let Some(callback) = self.info_callback.as_mut() {
+                        let info = arena.read().unwrap().search_info(
+                            self.head,
+                            &self.params,
+                            n_iterations,
+                            elapsed,
+                            self.carried_visits,
+                        );
+                        callback(&info);
+                    }
+                }
             }
-            // Asserts that the game is indeed terminated
-            // assert!(status == chess::BoardStatus::Checkmate || status == chess::BoardStatus::Stalemate);
-            (board.status(), board.side_to_move())
-        } else {
-            panic!(
-                "{} simulation policy not yet implemented.",
-                simulation_policy
-            );
-        }
+        });
+        self.arena = arena.into_inner().unwrap();
+        n_iterations.into_inner()
     }
 
-    fn backpropagate(&mut self, leaf: &WNodeRef, status: (chess::BoardStatus, chess::Color)) {
-        // Stop backpropagation is dead parent reached
-        if leaf.upgrade().is_none() {
-            return;
-        }
-        // Stop backpropagation if node already borrowed in DAG
-        if let Ok(mut mut_leaf_node_ref) = leaf.upgrade().unwrap().try_borrow_mut() {
-            // let leaf_ref = leaf.upgrade().unwrap();
-            // let mut mut_leaf_node_ref = (*leaf_ref).borrow_mut();
-            if let chess::BoardStatus::Checkmate = status.0 {
-                // White won
-                if let chess::Color::White = status.1 {
-                    mut_leaf_node_ref.white_wins += 1_f32;
+    /// Searches the tree on the calling thread while `n_threads - 1` workers search independent
+    /// trees of the same position. The workers' root statistics are summed up apart from the
+    /// tree, for the move decision.
+    fn _populate_tree_root_parallel(&mut self) -> usize {
+        debug!(
+            "Populate tree called with {} independent trees",
+            self.params.n_threads
+        );
+        let head_board = self.head_board();
+        let workers_stop_flag = AtomicBool::new(false);
+        let (n_iterations, worker_trees) = std::thread::scope(|scope| {
+            let workers = (1..self.params.n_threads)
+                .map(|_| {
+                    let params = self.params.clone();
+                    let policy_provider = self.policy_provider.clone();
+                    let workers_stop_flag = &workers_stop_flag;
+                    scope.spawn(move || {
+                        root_worker(head_board, &params, &*policy_provider, workers_stop_flag)
+                    })
+                })
+                .collect::<Vec<_>>();
+            let n_iterations = self._populate_tree();
+            workers_stop_flag.store(true, Ordering::Relaxed);
+            let worker_trees = workers
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .collect::<Vec<(Arena, usize)>>();
+            (n_iterations, worker_trees)
+        });
+        // Sum up the visits and wins of each move over all workers
+        let worker_root = self.worker_root.get_or_insert_with(Arena::default);
+        worker_trees
+            .iter()
+            .fold(n_iterations, |n_iterations, (arena, worker_iterations)| {
+                worker_root.add_root_statistics(arena, NodeId(0));
+                n_iterations + worker_iterations
+            })
+    }
+
+    /// Runs the playouts of each expanded node on `n_threads` workers, the tree itself is only
+    /// accessed from the calling thread.
+    fn _populate_tree_leaf_parallel(&mut self) -> usize {
+        debug!(
+            "Populate tree called with {} playouts per iteration",
+            self.params.n_threads
+        );
+        let now = Instant::now();
+        let params = self.params.clone();
+        let mut n_iterations: usize = 0;
+        let mut last_report: f32 = 0.;
+        std::thread::scope(|scope| {
+            let (result_sender, result_receiver) = mpsc::channel::<Playout>();
+            let board_senders = (0..params.n_threads)
+                .map(|_| {
+                    let (board_sender, board_receiver) = mpsc::channel::<chess::Board>();
+                    let result_sender = result_sender.clone();
+                    let params = &params;
+                    scope.spawn(move || {
+                        let mut rng = thread_rng();
+                        for board in board_receiver {
+                            if result_sender
+                                .send(simulate(board, params, &mut rng))
+                                .is_err()
+                            {
+                                break;
+                            }
+                        }
+                    });
+                    board_sender
+                })
+                .collect::<Vec<mpsc::Sender<chess::Board>>>();
+            loop {
+                let (selected_node, mut path) = self.arena.select(self.head, &self.params, 0);
+                let expanded_node = expand(
+                    &mut self.arena,
+                    selected_node,
+                    self.head,
+                    &self.params,
+                    &*self.policy_provider,
+                    &mut self.rng,
+                );
+                if expanded_node != selected_node {
+                    path.push(expanded_node);
                 }
-                // Black won
-                else {
-                    mut_leaf_node_ref.white_wins += 0_f32;
+                self.arena.solve(expanded_node);
+                // Play out the node once per worker and count each playout as an iteration
+                let board = self.node(expanded_node).board;
+                for board_sender in &board_senders {
+                    board_sender.send(board).unwrap();
+                }
+                for playout in result_receiver.iter().take(board_senders.len()) {
+                    self.arena.backpropagate(&path, &playout, &self.params);
+                }
+                n_iterations += board_senders.len();
+                // Time limit and external stop request
+                let elapsed = now.elapsed().as_secs_f32();
+                if self._search_finished(elapsed, n_iterations)
+                    || self._best_move_decided(&self.arena, n_iterations, elapsed)
+                {
+                    break;
+                }
+                // Periodic search report
+                if elapsed - last_report >= 1. {
+                    last_report = elapsed;
+                    self._report_search_info(n_iterations, elapsed);
                 }
             }
-            // Stalemate or simulation bound condition exceeded, ignores status.1
-            else {
-                mut_leaf_node_ref.white_wins += 0.5_f32;
-            }
-            // Update visit count
-            mut_leaf_node_ref.visits += 1;
-            // Update score
-            mut_leaf_node_ref.score = mut_leaf_node_ref.get_score(SelectionPolicy::default());
-            //Recursively backpropagate on parent nodes
-            // drop(mut_leaf_node_ref);
-            // let node_ref = leaf.upgrade().unwrap();
-            for parent in &(*mut_leaf_node_ref).parents {
-                self.backpropagate(parent, status); // Backpropagate to each parent
-            }
+            // Closing the channels lets the workers return
+            drop(board_senders);
+        });
+        n_iterations
+    }
+
+    fn _best_move_decided(&self, arena: &Arena, n_iterations: usize, elapsed: f32) -> bool {
+        // A proven head needs no further search, unless a quicker mate may still be proven
+        if arena.proof_settled(self.head) {
+            return true;
+        }
+        if !self.params.early_stop || elapsed <= 0. {
+            return false;
         }
+        // Iterations expected until the time limit, at the current search speed
+        let remaining_time = self.params.max_search_time - elapsed;
+        let mut remaining_iterations = n_iterations as f32 / elapsed * remaining_time;
+        if let Some(max_iterations) = self.params.max_iterations {
+            remaining_iterations =
+                remaining_iterations.min(max_iterations.saturating_sub(n_iterations) as f32);
+        }
+        arena.best_child_unassailable(self.head, remaining_iterations)
     }
 
-    fn add_children(&mut self, parent: &NodeRef, boards: impl Iterator<Item = chess::Board>) {
-        // Allocate a vector of all nodes for the given boards (size hint correct due to chess crate implentation)
-        let mut child_nodes = Vec::<NodeRef>::with_capacity(boards.size_hint().0);
-        child_nodes.extend(
-            boards
-                .map(|x| {
-                    // Create the candidate node
-                    let mut cand_node = NodeRef::new(Node::new(Some(parent.clone()), x));
-                    // Check if it is already tracked in the tree's hashset
-                    if let Some(existing_cand_node) = self.nodes.get(&cand_node) {
-                        // If so, append parent to target node's parent refs
-                        (**existing_cand_node)
-                            .borrow_mut()
-                            .add_parent_node(parent.clone().downgrade());
-                        cand_node = (*existing_cand_node).clone();
-                    } else {
-                        self.nodes.insert(cand_node.clone());
-                    }
-                    cand_node
-                })
-                .collect::<Vec<NodeRef>>(),
-        );
-        // Assign children vector to parent
-        (**parent).borrow_mut().children = child_nodes;
+    fn _search_finished(&self, elapsed: f32, n_iterations: usize) -> bool {
+        // Time and iteration limits
+        if elapsed > self.params.max_search_time
+            || self
+                .params
+                .max_iterations
+                .is_some_and(|x| n_iterations >= x)
+        {
+            return true;
+        }
+        // External stop request
+        self.stop_flag
+            .as_ref()
+            .is_some_and(|stop_flag| stop_flag.load(Ordering::Relaxed))
+    }
+}
+
+/// Runs one selection, expansion, simulation and backpropagation step below `head`.
+fn search_iteration(
+    arena: &mut Arena,
+    head: NodeId,
+    params: &TreeParams,
+    policy_provider: &dyn PolicyProvider,
+    rng: &mut impl Rng,
+) {
+    let (selected_node, mut path) = arena.select(head, params, 0);
+    let expanded_node = expand(arena, selected_node, head, params, policy_provider, rng);
+    if expanded_node != selected_node {
+        path.push(expanded_node);
+    }
+    arena.solve(expanded_node);
+    let playout = simulate(arena.node(expanded_node).board, params, rng);
+    arena.backpropagate(&path, &playout, params);
+}
+
+/// Searches an independent tree of `board` until `stop_flag` is raised.
+fn root_worker(
+    board: chess::Board,
+    params: &TreeParams,
+    policy_provider: &dyn PolicyProvider,
+    stop_flag: &AtomicBool,
+) -> (Arena, usize) {
+    let mut arena = Arena::default();
+    let head = arena.add_node(Node::new(board, 0));
+    let mut rng = thread_rng();
+    let mut n_iterations: usize = 0;
+    while !stop_flag.load(Ordering::Relaxed) {
+        search_iteration(&mut arena, head, params, policy_provider, &mut rng);
+        n_iterations += 1;
     }
+    (arena, n_iterations)
+}
+
+fn expand(
+    arena: &mut Arena,
+    root: NodeId,
+    head: NodeId,
+    params: &TreeParams,
+    policy_provider: &dyn PolicyProvider,
+    rng: &mut impl Rng,
+) -> NodeId {
+    // Root reached through a cycle, already expanded
+    if arena.node(root)._has_children() {
+        return root;
+    }
+    // If target depth not yet reached
+    if arena.node(root).depth < params.max_search_depth + arena.node(head).depth {
+        // Add each legal move to the tree
+        let children = child_boards(&arena.node(root).board, policy_provider);
+        arena.add_children(root, children.into_iter());
+    }
+    // If expansion created new children
+    if arena.node(root)._has_children() {
+        *arena.node(root).children.choose(rng).unwrap()
+    }
+    // Target depth reached or terminal board state reached
+    else {
+        root
+    }
+}
 
-    fn drop_node(nodes: &mut HashSet<NodeRef>, root: &NodeRef) {
-        // Removes hashset reference
-        nodes.remove(root);
+/// Runs search iterations on a tree shared with other workers until `stop_flag` is raised.
+fn search_worker(
+    arena: &RwLock<Arena>,
+    head: NodeId,
+    params: &TreeParams,
+    policy_provider: &dyn PolicyProvider,
+    stop_flag: &AtomicBool,
+    n_iterations: &AtomicUsize,
+) {
+    let mut rng = thread_rng();
+    while !stop_flag.load(Ordering::Relaxed) {
+        // Selection only reads the tree structure, statistics are updated atomically
+        let (selected_node, mut path) =
+            arena
+                .read()
+                .unwrap()
+                .select(head, params, params.virtual_loss);
+        let (board, depth, is_expanded) = {
+            let arena = arena.read().unwrap();
+            let node = arena.node(selected_node);
+            (node.board, node.depth, node._has_children())
+        };
+        // Expansion, priors are computed before locking the tree
+        if !is_expanded && depth < params.max_search_depth + arena.read().unwrap().node(head).depth
         {
-            // Start recursive drop call on child nodes only reachable through root
-            if let Ok(root_ref) = (*root).try_borrow() {
-                // Iterate over all child nodes
-                root_ref.children.iter().for_each(|x| {
-                    // Check if child not already mut borrowed in other recursive drop line
-                    if let Ok(mut mut_child_ref) = (*x).try_borrow_mut() {
-                        // Clean parent refs of root and already dropped parents
-                        mut_child_ref.parents.retain(|y| {
-                            let result = if (*y).upgrade().is_some() {
-                                *(*y).upgrade().unwrap() != **root
-                            } else {
-                                false
-                            };
-                            result
-                        });
-                    }
-                    // Drop all child nodes that have no more parents
-                    if (**x).borrow().parents.len() == 0 {
-                        Self::drop_node(nodes, &x)
-                    }
-                });
+            let children = child_boards(&board, policy_provider);
+            let mut arena = arena.write().unwrap();
+            // Another worker may have expanded the node in the meantime
+            if !arena.node(selected_node)._has_children() {
+                arena.add_children(selected_node, children.into_iter());
             }
         }
-        {
-            // Remove all child and parent refs originating from root
-            if let Ok(mut mut_root_ref) = (*root).try_borrow_mut() {
-                (*mut_root_ref).parents.clear();
-                (*mut_root_ref).children.clear();
+        let leaf_board = {
+            let arena = arena.read().unwrap();
+            let expanded_node = match arena.node(selected_node).children.choose(&mut rng) {
+                Some(child) if !is_expanded => {
+                    arena.node(*child).add_virtual_loss(params.virtual_loss);
+                    path.push(*child);
+                    *child
+                }
+                // Target depth reached, terminal board state reached or root reached through a
+                // cycle
+                _ => selected_node,
+            };
+            arena.solve(expanded_node);
+            arena.node(expanded_node).board
+        };
+        // Playouts run without holding the lock, so that the other workers' expansions do not
+        // wait for them. Node ids stay valid, the arena only grows during the search.
+        let playout = simulate(leaf_board, params, &mut rng);
+        let arena = arena.read().unwrap();
+        arena.backpropagate(&path, &playout, params);
+        for node_id in path {
+            arena.node(node_id).remove_virtual_loss(params.virtual_loss);
+        }
+        n_iterations.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Boards reachable from `board` in one legal move, in move generation order, with their priors.
+fn child_boards(
+    board: &chess::Board,
+    policy_provider: &dyn PolicyProvider,
+) -> Vec<(chess::Board, f32)> {
+    // Get root legal moves
+    let available_moves: Vec<chess::ChessMove> = chess::MoveGen::new_legal(board).collect();
+    // Gather each move's prior
+    let priors = policy_provider.priors(board, &available_moves);
+    available_moves
+        .iter()
+        .map(|x| board.make_move_new(*x))
+        .zip(priors)
+        .collect()
+}
+
+/// Outcome of a playout, along with its moves when RAVE needs them.
+struct Playout {
+    outcome: GameOutcome,
+    moves: Vec<chess::ChessMove>,
+}
+
+fn simulate(mut board: chess::Board, params: &TreeParams, rng: &mut impl Rng) -> Playout {
+    let mut target_board = chess::Board::default();
+    let mut moves = Vec::<chess::ChessMove>::new();
+    for _ in 0..params.n_cutoff_moves {
+        match crate::mcts::playout::choose_move(&board, params.simulation_policy, rng) {
+            Some(chess_move) => {
+                board.make_move(chess_move, &mut target_board);
+                std::mem::swap(&mut board, &mut target_board);
+                if params.rave.is_some() {
+                    moves.push(chess_move);
+                }
             }
+            None => break,
+        }
+    }
+    // Cutoff reached, estimate the outcome of an unfinished game
+    let outcome = GameOutcome::from_board(&board).unwrap_or_else(|| {
+        GameOutcome::Estimated(crate::mcts::evaluation::win_probability(&board))
+    });
+    Playout { outcome, moves }
+}
+
+/// All-moves-as-first statistics of a move, gathered under RAVE.
+#[derive(Default)]
+pub struct AmafStats {
+    visits: AtomicUsize,
+    // Bits of an `f32`, like the node's wins
+    white_wins: AtomicU32,
+}
+
+impl AmafStats {
+    pub fn visits(&self) -> usize {
+        self.visits.load(Ordering::Relaxed)
+    }
+
+    fn add_white_wins(&self, white_wins: f32) {
+        let _ = self
+            .white_wins
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                Some((f32::from_bits(x) + white_wins).to_bits())
+            });
+        self.visits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// AMAF wins of the player making the move.
+    pub fn wins(&self, color: chess::Color) -> f32 {
+        let white_wins = f32::from_bits(self.white_wins.load(Ordering::Relaxed));
+        match color {
+            chess::Color::White => white_wins,
+            chess::Color::Black => self.visits() as f32 - white_wins,
         }
-        // Memory should now be free as no more references point to the nodes
     }
 }
 
 pub struct Node {
-    parents: Vec<WNodeRef>,
-    pub children: Vec<NodeRef>,
+    parents: Vec<NodeId>,
+    pub children: Vec<NodeId>,
     board: chess::Board,
     pub depth: usize,
-    visits: usize,
-    white_wins: f32,
-    score: f32,
+    visits: AtomicUsize,
+    // Bits of an `f32`, atomics allow concurrent updates from the search workers
+    white_wins: AtomicU32,
+    // Pending playouts of the search workers, counted as losses while selecting
+    virtual_loss: AtomicUsize,
+    prior: f32,
+    // `Proof` found by the solver, `PROOF_NONE` until then
+    proof: AtomicU8,
+    // Plies until the mate along the quickest proven line, once proven
+    proof_plies: AtomicUsize,
+    // Visits when the proof or its plies were last set
+    proof_visits: AtomicUsize,
+    // All-moves-as-first statistics of the moves to `children`, in the same order, gathered
+    // under RAVE. Kept on the edges since transposed children are shared by several parents
+    amaf: Vec<AmafStats>,
 }
 
 impl Node {
-    pub fn new(parent: Option<NodeRef>, board: chess::Board) -> Node {
-        let depth: usize;
-        let mut parents: Vec<WNodeRef> = Vec::new();
-        if let Some(parent) = parent {
-            depth = (*parent).borrow_mut().depth + 1;
-            parents.push(parent.downgrade() as WNodeRef);
-        } else {
-            depth = 0;
-        }
-
+    pub fn new(board: chess::Board, depth: usize) -> Node {
         Node {
-            parents: parents,
-            children: Vec::<NodeRef>::new(),
-            board: board,
-            depth: depth,
-            visits: 0,
-            white_wins: 0.,
-            score: f32::INFINITY,
+            parents: Vec::<NodeId>::new(),
+            children: Vec::<NodeId>::new(),
+            board,
+            depth,
+            visits: AtomicUsize::new(0),
+            white_wins: AtomicU32::new(0_f32.to_bits()),
+            virtual_loss: AtomicUsize::new(0),
+            prior: 1.,
+            proof: AtomicU8::new(PROOF_NONE),
+            proof_plies: AtomicUsize::new(0),
+            proof_visits: AtomicUsize::new(0),
+            amaf: Vec::<AmafStats>::new(),
         }
     }
 
-    fn add_parent_node(&mut self, parent: WNodeRef) {
-        self.parents.push(parent);
+    pub fn board(&self) -> chess::Board {
+        self.board
     }
 
-    fn _has_children(&self) -> bool {
-        self.children.len() > 0
+    pub fn visits(&self) -> usize {
+        self.visits.load(Ordering::Relaxed)
     }
 
-    pub fn get_score(&self, selection_policy: SelectionPolicy) -> f32 {
-        // Gather sum of all parent visits
-        let mut parent_visits: usize = self
-            .parents
-            .iter()
-            // Remove dropped parents
-            .filter(|x| x.upgrade().is_some())
-            .map(|x| unsafe {
-                // Unsafe bc node mutably borrowed in backpropagate, avoids
-                // dropping mutref to create ref instead
-                (*(*x).upgrade().unwrap().as_ptr()).visits
-            })
-            .sum();
-        // Case where one of multiple parents (n_parents > 1) is yet unexplored
-        if parent_visits == 0 {
-            parent_visits = 1;
-        }
-        // Pure win ratio score
-        let win_score = self.get_win_ratio(None);
-        if let SelectionPolicy::UCT = selection_policy {
-            let c: f32 = f32::sqrt(2.0);
-            let score: f32;
-            if self.visits > 0 {
-                score = win_score + c * ((parent_visits as f32).ln() / self.visits as f32).sqrt();
-            } else {
-                // Unvisited children should be explored with high priority
-                score = f32::INFINITY;
-            }
-            if score.is_nan() {
-                panic!("This should never happen: Win score {win_score}, parent_visits {parent_visits}, child visits {}, ln(parent_visits){}.", self.visits, (parent_visits as f32).ln());
-            }
-            score
-        } else {
-            panic!("{} selection policy not yet implemented.", selection_policy);
+    fn white_wins(&self) -> f32 {
+        f32::from_bits(self.white_wins.load(Ordering::Relaxed))
+    }
+
+    fn add_white_wins(&self, white_wins: f32) {
+        let _ = self
+            .white_wins
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                Some((f32::from_bits(x) + white_wins).to_bits())
+            });
+    }
+
+    fn add_virtual_loss(&self, virtual_loss: usize) {
+        if virtual_loss > 0 {
+            self.virtual_loss.fetch_add(virtual_loss, Ordering::Relaxed);
         }
     }
 
-    fn get_win_ratio(&self, color_to_play: Option<chess::Color>) -> f32 {
-        debug_assert!(
-            self.board.side_to_move() == color_to_play.unwrap_or(self.board.side_to_move())
-        );
-        if self.board.side_to_move() == chess::Color::White {
-            self.white_wins / (self.visits as f32)
-        } else {
-            1_f32 - (self.white_wins / self.visits as f32)
+    fn remove_virtual_loss(&self, virtual_loss: usize) {
+        if virtual_loss > 0 {
+            self.virtual_loss.fetch_sub(virtual_loss, Ordering::Relaxed);
         }
     }
 
-    fn _is_not_terminal(&self) -> bool {
-        self.board.status() == chess::BoardStatus::Ongoing
+    /// Result proven by the solver, for the player who moved into this node.
+    pub fn proof(&self) -> Option<Proof> {
+        match self.proof.load(Ordering::Relaxed) {
+            PROOF_WIN => Some(Proof::Win),
+            PROOF_LOSS => Some(Proof::Loss),
+            _ => None,
+        }
     }
 
-    fn _get_move_diff(parent_node: NodeRef, child_node: NodeRef) -> chess::ChessMove {
-        let current_board = (*parent_node).borrow().board;
-        let mut move_generator = chess::MoveGen::new_legal(&current_board);
-        let potential_move_ids = (*parent_node)
-            .borrow()
-            .children
-            .iter()
-            .map(|x| (**x).borrow().board)
-            .enumerate()
-            .filter(|(_, board)| *board == (*child_node).borrow().board)
-            .collect::<Vec<(usize, chess::Board)>>();
-        assert!(potential_move_ids.len() == 1);
-        let move_id = potential_move_ids[0].0;
-        move_generator.nth(move_id).unwrap()
+    /// Plies until the mate of a proven node, 0 for a checkmate.
+    pub fn proof_plies(&self) -> usize {
+        self.proof_plies.load(Ordering::Relaxed)
     }
-    // #![cfg(debug_assertions)]
-    // fn _check_move_tracking_mismatch(&self, available_moves: Vec<chess::ChessMove>, node_children: &Vec<NodeRef>){
-    //     for (mv, node) in available_moves.iter().zip(node_children.iter()){
-    //         assert!(mv == &(**node).borrow().board.get_move());
 
-    // }
-}
+    fn set_proof(&self, proof: Proof, plies: usize) {
+        let proof = match proof {
+            Proof::Win => PROOF_WIN,
+            Proof::Loss => PROOF_LOSS,
+        };
+        self.proof_plies.store(plies, Ordering::Relaxed);
+        self.proof_visits.store(self.visits(), Ordering::Relaxed);
+        self.proof.store(proof, Ordering::Relaxed);
+    }
 
-impl Hash for Node {
-    fn hash<H: Hasher>(&self, hasher_state: &mut H) {
-        self.board.get_hash().hash(hasher_state);
+    fn _has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    pub fn get_score(
+        &self,
+        parent_visits: usize,
+        selection_policy: SelectionPolicy,
+        exploration_constant: f32,
+        rave: Option<(RaveSchedule, &AmafStats)>,
+    ) -> f32 {
+        // Virtual losses count as visits without any win
+        let visits = self.visits() + self.virtual_loss.load(Ordering::Relaxed);
+        // AMAF win ratio of the edge to this node, once the move was made in a playout under RAVE
+        let amaf_score = rave
+            .filter(|(_, amaf)| amaf.visits() > 0)
+            .map(|(schedule, amaf)| {
+                let amaf_visits = amaf.visits();
+                let amaf_wins = amaf.wins(!self.board.side_to_move());
+                (schedule, amaf_visits, amaf_wins / amaf_visits as f32)
+            });
+        // Pure win ratio score, blended with the AMAF one under RAVE
+        let mut win_score = self.get_wins() / visits as f32;
+        if let Some((schedule, amaf_visits, amaf_score)) = amaf_score {
+            let beta = schedule.beta(visits, amaf_visits);
+            win_score = (1_f32 - beta) * win_score + beta * amaf_score;
+        }
+        let score = match selection_policy {
+            SelectionPolicy::UCT => {
+                let c = exploration_constant;
+                if visits > 0 {
+                    win_score + c * ((parent_visits as f32).ln() / visits as f32).sqrt()
+                } else {
+                    // Unvisited children should be explored with high priority
+                    f32::INFINITY
+                }
+            }
+            SelectionPolicy::AlphaZero => {
+                let c_puct = exploration_constant;
+                // Unvisited children are valued by their AMAF win ratio or as draws, and ranked by
+                // their prior
+                let value = match amaf_score {
+                    _ if visits > 0 => win_score,
+                    Some((_, _, amaf_score)) => amaf_score,
+                    None => 0.5,
+                };
+                value + c_puct * self.prior * (parent_visits as f32).sqrt() / (1 + visits) as f32
+            }
+        };
+        // Corrupted statistics rank last instead of aborting the search
+        if score.is_nan() {
+            debug!(
+                "NaN score: win score {win_score}, parent visits {parent_visits}, visits {visits}"
+            );
+            return f32::NEG_INFINITY;
+        }
+        score
     }
-}
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.board.get_hash() == other.board.get_hash()
+    /// Win ratio of the player who moved into this node.
+    fn get_win_ratio(&self) -> f32 {
+        self.get_wins() / self.visits() as f32
     }
 
-    fn ne(&self, other: &Self) -> bool {
-        self.board.get_hash() != other.board.get_hash()
+    // Wins of the player who moved into this node
+    fn get_wins(&self) -> f32 {
+        match !self.board.side_to_move() {
+            chess::Color::White => self.white_wins(),
+            chess::Color::Black => self.visits() as f32 - self.white_wins(),
+        }
     }
 }
 
-impl Eq for Node {}
+/// Game result proven by the solver, seen from the player who moved into a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Proof {
+    Win,
+    Loss,
+}
 
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
 pub enum SelectionPolicy {
     #[default]
     UCT,
@@ -505,16 +1573,610 @@ impl fmt::Display for ExpansionPolicy {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SimulationPolicy {
     #[default]
     Random,
+    /// Plays the best capture by MVV-LVA, random moves otherwise
+    CaptureFirst,
+    /// Plays checks, then captures, then random moves
+    CheckFirst,
+    /// Plays the move with the best static evaluation, a random one with probability epsilon
+    EpsilonGreedy(f32),
 }
 
 impl fmt::Display for SimulationPolicy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             SimulationPolicy::Random => write!(f, "Random"),
+            SimulationPolicy::CaptureFirst => write!(f, "CaptureFirst"),
+            SimulationPolicy::CheckFirst => write!(f, "CheckFirst"),
+            SimulationPolicy::EpsilonGreedy(epsilon) => write!(f, "EpsilonGreedy({epsilon})"),
+        }
+    }
+}
+
+/// How the move to play is chosen among the head's visited children once the search is over.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MoveSelection {
+    /// Most visited move (robust child)
+    #[default]
+    MaxVisits,
+    /// Move with the best win ratio (max child)
+    MaxValue,
+    /// Move which is both the most visited and the best valued, searched on for a while if they
+    /// differ (robust-max child)
+    RobustMax,
+    /// Move with the best win ratio minus `c / sqrt(visits)` (secure child)
+    LowerConfidenceBound(f32),
+}
+
+impl fmt::Display for MoveSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveSelection::MaxVisits => write!(f, "MaxVisits"),
+            MoveSelection::MaxValue => write!(f, "MaxValue"),
+            MoveSelection::RobustMax => write!(f, "RobustMax"),
+            MoveSelection::LowerConfidenceBound(c) => write!(f, "LowerConfidenceBound({c})"),
+        }
+    }
+}
+
+/// How a search is spread over several threads.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParallelMode {
+    /// All threads search one shared tree, diversified by virtual losses
+    #[default]
+    Tree,
+    /// Each thread searches its own tree, visits and wins of the root's moves are summed up
+    Root,
+    /// Each expanded node is played out once per thread
+    Leaf,
+}
+
+impl fmt::Display for ParallelMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParallelMode::Tree => write!(f, "Tree"),
+            ParallelMode::Root => write!(f, "Root"),
+            ParallelMode::Leaf => write!(f, "Leaf"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::search::MAX_SEARCH_DEPTH;
+    use std::str::FromStr;
+
+    /// Arena holding `board` as its head, expanded with uniform priors.
+    fn expanded_arena(board: chess::Board) -> (Arena, NodeId) {
+        let mut arena = Arena::default();
+        let head = arena.add_node(Node::new(board, 0));
+        let children = child_boards(&board, &UniformPolicy);
+        arena.add_children(head, children.into_iter());
+        (arena, head)
+    }
+
+    /// Child of `head` reached by the UCI move `uci`.
+    fn child_of(arena: &Arena, head: NodeId, uci: &str) -> NodeId {
+        let chess_move = chess::ChessMove::from_str(uci).unwrap();
+        arena.node_ids[&arena.node(head).board.make_move_new(chess_move).get_hash()]
+    }
+
+    /// Sets the visits of `node_id` and the wins of the player who moved into it.
+    fn set_stats(arena: &Arena, node_id: NodeId, visits: usize, wins: f32) {
+        let node = arena.node(node_id);
+        let white_wins = match !node.board.side_to_move() {
+            chess::Color::White => wins,
+            chess::Color::Black => visits as f32 - wins,
+        };
+        node.visits.store(visits, Ordering::Relaxed);
+        node.white_wins
+            .store(white_wins.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Prior of 0.9 on `favorite`, the other moves share the rest.
+    struct FavoriteMove(chess::ChessMove);
+
+    impl PolicyProvider for FavoriteMove {
+        fn priors(&self, _board: &chess::Board, moves: &[chess::ChessMove]) -> Vec<f32> {
+            let other_prior = 0.1 / (moves.len() - 1) as f32;
+            moves
+                .iter()
+                .map(|x| if *x == self.0 { 0.9 } else { other_prior })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn priors_rank_unvisited_children_under_puct() {
+        let mut likely = Node::new(chess::Board::default(), 1);
+        likely.prior = 0.8;
+        let mut unlikely = Node::new(chess::Board::default(), 1);
+        unlikely.prior = 0.2;
+        let score = |node: &Node, policy| node.get_score(10, policy, 1.5, None);
+        assert!(
+            score(&likely, SelectionPolicy::AlphaZero)
+                > score(&unlikely, SelectionPolicy::AlphaZero)
+        );
+        // UCT ignores priors
+        assert_eq!(
+            score(&likely, SelectionPolicy::UCT),
+            score(&unlikely, SelectionPolicy::UCT)
+        );
+    }
+
+    #[test]
+    fn nan_scores_rank_last() {
+        let node = Node::new(chess::Board::default(), 1);
+        node.visits.store(1, Ordering::Relaxed);
+        node.add_white_wins(f32::NAN);
+        for policy in [SelectionPolicy::UCT, SelectionPolicy::AlphaZero] {
+            assert_eq!(node.get_score(10, policy, 1.5, None), f32::NEG_INFINITY);
+        }
+    }
+
+    #[test]
+    fn policy_provider_steers_the_puct_search() {
+        let favorite = chess::ChessMove::from_str("a2a3").unwrap();
+        let board = chess::Board::default();
+        let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(f32::MAX);
+        tree.set_max_iterations(Some(300));
+        tree.set_selection_policy(SelectionPolicy::AlphaZero);
+        tree.set_policy_provider(Box::new(FavoriteMove(favorite)));
+        assert_eq!(tree.yield_best_move(chess::Color::White), Some(favorite));
+    }
+
+    #[test]
+    fn children_get_the_priors_of_their_moves() {
+        let favorite = chess::ChessMove::from_str("g1f3").unwrap();
+        let board = chess::Board::default();
+        let mut arena = Arena::default();
+        let head = arena.add_node(Node::new(board, 0));
+        let children = child_boards(&board, &FavoriteMove(favorite));
+        arena.add_children(head, children.into_iter());
+        let favorite_child = arena.node_ids[&board.make_move_new(favorite).get_hash()];
+        for child in &arena.node(head).children {
+            let expected_prior = if *child == favorite_child {
+                0.9
+            } else {
+                0.1 / 19_f32
+            };
+            assert_eq!(arena.node(*child).prior, expected_prior);
         }
     }
+
+    #[test]
+    fn leading_child_is_unassailable_once_out_of_reach() {
+        let (arena, head) = expanded_arena(chess::Board::default());
+        for (node_id, visits) in arena.node(head).children.iter().zip([100, 40, 10]) {
+            arena.node(*node_id).visits.store(visits, Ordering::Relaxed);
+        }
+        assert!(arena.best_child_unassailable(head, 59_f32));
+        assert!(!arena.best_child_unassailable(head, 60_f32));
+    }
+
+    #[test]
+    fn early_stop_ends_the_search_of_a_forced_move() {
+        let board = chess::Board::from_str("7k/R7/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(chess::MoveGen::new_legal(&board).len(), 1);
+        let n_iterations = |early_stop| {
+            let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+            tree.set_max_search_time(f32::MAX);
+            tree.set_max_iterations(Some(2_000));
+            tree.set_early_stop(early_stop);
+            tree.yield_best_move(chess::Color::Black);
+            tree.last_search_info().unwrap().iterations
+        };
+        assert!(n_iterations(true) < 2_000);
+        assert_eq!(n_iterations(false), 2_000);
+    }
+
+    #[test]
+    fn game_over_positions_have_no_move() {
+        let board = chess::Board::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+        assert_eq!(tree.yield_best_move(chess::Color::Black), None);
+        assert!(tree.last_search_info().is_none());
+    }
+
+    #[test]
+    fn move_selections_pick_their_child() {
+        let (arena, head) = expanded_arena(chess::Board::default());
+        let most_visited = child_of(&arena, head, "e2e4");
+        let balanced = child_of(&arena, head, "d2d4");
+        let best_valued = child_of(&arena, head, "g1f3");
+        set_stats(&arena, most_visited, 100, 55_f32);
+        set_stats(&arena, balanced, 40, 26_f32);
+        set_stats(&arena, best_valued, 4, 4_f32);
+        let best_child = |move_selection| arena.best_child(head, move_selection);
+        assert_eq!(best_child(MoveSelection::MaxVisits), Some(most_visited));
+        assert_eq!(best_child(MoveSelection::MaxValue), Some(best_valued));
+        // No move is both the most visited and the best valued one
+        assert_eq!(best_child(MoveSelection::RobustMax), None);
+        // Win ratio minus 1.5 / sqrt(visits): 0.4, 0.41 and 0.25
+        assert_eq!(
+            best_child(MoveSelection::LowerConfidenceBound(1.5)),
+            Some(balanced)
+        );
+        // The most visited move is robust once it is also the best valued one
+        set_stats(&arena, most_visited, 100, 100_f32);
+        assert_eq!(best_child(MoveSelection::RobustMax), Some(most_visited));
+    }
+
+    #[test]
+    fn ties_are_broken_by_visits() {
+        let (arena, head) = expanded_arena(chess::Board::default());
+        let rarely_visited = child_of(&arena, head, "e2e4");
+        let often_visited = child_of(&arena, head, "d2d4");
+        set_stats(&arena, rarely_visited, 10, 5_f32);
+        set_stats(&arena, often_visited, 20, 10_f32);
+        for move_selection in [
+            MoveSelection::MaxValue,
+            MoveSelection::RobustMax,
+            MoveSelection::LowerConfidenceBound(0_f32),
+        ] {
+            assert_eq!(arena.best_child(head, move_selection), Some(often_visited));
+        }
+        // Equally visited moves are told apart by their value
+        set_stats(&arena, rarely_visited, 20, 12_f32);
+        assert_eq!(
+            arena.best_child(head, MoveSelection::MaxVisits),
+            Some(rarely_visited)
+        );
+    }
+
+    #[test]
+    fn unvisited_and_lost_children_are_never_picked() {
+        let (mut arena, head) = expanded_arena(chess::Board::default());
+        let move_selections = [
+            MoveSelection::MaxVisits,
+            MoveSelection::MaxValue,
+            MoveSelection::RobustMax,
+            MoveSelection::LowerConfidenceBound(1_f32),
+        ];
+        for move_selection in move_selections {
+            assert_eq!(arena.best_child(head, move_selection), None);
+        }
+        let likely = child_of(&arena, head, "e2e4");
+        arena.node_mut(likely).prior = 0.99;
+        let visited = child_of(&arena, head, "a2a3");
+        let lost = child_of(&arena, head, "h2h3");
+        set_stats(&arena, visited, 1, 0_f32);
+        set_stats(&arena, lost, 50, 50_f32);
+        arena.node(lost).set_proof(Proof::Loss, 2);
+        for move_selection in move_selections {
+            assert_eq!(arena.best_child(head, move_selection), Some(visited));
+        }
+    }
+
+    #[test]
+    fn robust_max_searches_on_until_a_move_is_robust() {
+        let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(f32::MAX);
+        tree.set_max_iterations(Some(100));
+        let children = child_boards(&tree.head_board(), &UniformPolicy);
+        tree.arena.add_children(tree.head, children.into_iter());
+        let most_visited = child_of(&tree.arena, tree.head, "e2e4");
+        // Already robust, no further search
+        set_stats(&tree.arena, most_visited, 100, 100_f32);
+        assert_eq!(tree._extend_search_for_robust_max(), 0);
+        // A better valued move makes the search go on, in rounds of 5 iterations for at most a
+        // quarter of the iterations
+        set_stats(&tree.arena, most_visited, 100, 0_f32);
+        let best_valued = child_of(&tree.arena, tree.head, "d2d4");
+        set_stats(&tree.arena, best_valued, 1, 1_f32);
+        let n_iterations = tree._extend_search_for_robust_max();
+        assert!(
+            (5..=25).contains(&n_iterations),
+            "{n_iterations} iterations"
+        );
+        assert_eq!(tree.params.max_iterations, Some(100));
+    }
+
+    #[test]
+    fn played_move_was_visited() {
+        for move_selection in [
+            MoveSelection::MaxVisits,
+            MoveSelection::MaxValue,
+            MoveSelection::RobustMax,
+            MoveSelection::LowerConfidenceBound(1_f32),
+        ] {
+            let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+            tree.set_max_search_time(f32::MAX);
+            tree.set_max_iterations(Some(3));
+            tree.set_move_selection(move_selection);
+            tree.yield_best_move(chess::Color::White);
+            // The played move became the head
+            assert!(tree.node(tree.head()).visits() > 0);
+        }
+    }
+
+    #[test]
+    fn root_statistics_add_up_over_the_trees() {
+        let board = chess::Board::default();
+        let trees: Vec<(Arena, NodeId)> = (0..3).map(|_| expanded_arena(board)).collect();
+        for (i, (arena, head)) in trees.iter().enumerate() {
+            set_stats(arena, *head, 10 * (i + 1), 0_f32);
+            set_stats(arena, child_of(arena, *head, "e2e4"), 4 * (i + 1), i as f32);
+        }
+        let mut merged_root = Arena::default();
+        for (arena, head) in &trees {
+            merged_root.add_root_statistics(arena, *head);
+        }
+        let head = merged_root.node(NodeId(0));
+        assert_eq!(head.children.len(), 20);
+        assert_eq!(head.visits(), 60);
+        let e4 = merged_root.node(child_of(&merged_root, NodeId(0), "e2e4"));
+        assert_eq!(e4.visits(), 24);
+        assert_eq!(e4.get_wins(), 3_f32);
+        assert_eq!(
+            merged_root
+                .node(child_of(&merged_root, NodeId(0), "d2d4"))
+                .visits(),
+            0
+        );
+    }
+
+    #[test]
+    fn root_parallel_search_plays_a_visited_move() {
+        let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(f32::MAX);
+        tree.set_max_iterations(Some(300));
+        tree.set_early_stop(false);
+        tree.set_n_threads(3);
+        tree.set_parallel_mode(ParallelMode::Root);
+        let n_iterations = tree._populate();
+        // The tree keeps the iterations of the calling thread only
+        assert_eq!(tree.node(tree.head).visits(), 300);
+        // The merged root counts the iterations of all trees
+        let merged_root = tree._merged_root().unwrap();
+        let head = merged_root.node(NodeId(0));
+        assert_eq!(head.visits(), n_iterations);
+        let children_visits: usize = head
+            .children
+            .iter()
+            .map(|x| merged_root.node(*x).visits())
+            .sum();
+        assert_eq!(children_visits, n_iterations);
+        let chess_move = tree._yield_best_move(chess::Color::White);
+        assert!(chess::Board::default().legal(chess_move));
+        let played_child = child_of(&merged_root, NodeId(0), &chess_move.to_string());
+        assert!(merged_root.node(played_child).visits() > 0);
+        assert!(tree.worker_root.is_none());
+    }
+
+    #[test]
+    fn leaf_parallel_search_plays_out_each_leaf_once_per_worker() {
+        let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(f32::MAX);
+        tree.set_max_iterations(Some(3));
+        tree.set_early_stop(false);
+        tree.set_n_threads(3);
+        tree.set_parallel_mode(ParallelMode::Leaf);
+        // A single expansion, played out by each of the workers
+        assert_eq!(tree._populate(), 3);
+        assert_eq!(tree.node(tree.head).visits(), 3);
+        let leaf_visits: Vec<usize> = tree
+            .node(tree.head)
+            .children
+            .iter()
+            .map(|x| tree.node(*x).visits())
+            .filter(|x| *x > 0)
+            .collect();
+        assert_eq!(leaf_visits, vec![3]);
+        // Further searches play a legal and visited move
+        tree.set_max_iterations(Some(60));
+        let chess_move = tree.yield_best_move(chess::Color::White).unwrap();
+        assert!(chess::Board::default().legal(chess_move));
+        assert!(tree.node(tree.head()).visits() > 0);
+    }
+
+    #[test]
+    fn cut_off_playouts_are_scored_by_the_evaluation() {
+        // White is a queen up, no playout of a few plies ends the game
+        let board = chess::Board::from_str("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+        let mut rng = StdRng::seed_from_u64(0);
+        tree.set_n_cutoff_moves(0);
+        let playout = simulate(board, &tree.params, &mut rng);
+        let win_probability = crate::mcts::evaluation::win_probability(&board);
+        assert_eq!(playout.outcome, GameOutcome::Estimated(win_probability));
+        tree.set_n_cutoff_moves(2);
+        for _ in 0..10 {
+            match simulate(board, &tree.params, &mut rng).outcome {
+                GameOutcome::Estimated(white_score) => assert!(white_score > 0.9),
+                outcome => panic!("Unfinished playout scored as {outcome:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn playouts_are_credited_along_their_path_only() {
+        // 1. Nf3 Nf6 2. Nc3 and 1. Nc3 Nf6 2. Nf3 transpose into the same node
+        let tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        let (mut arena, head) = expanded_arena(chess::Board::default());
+        let expand_child = |arena: &mut Arena, parent: NodeId, uci: &str| {
+            let child = child_of(arena, parent, uci);
+            let children = child_boards(&arena.node(child).board, &UniformPolicy);
+            arena.add_children(child, children.into_iter());
+            child
+        };
+        let knight_f3 = expand_child(&mut arena, head, "g1f3");
+        let knight_c3 = expand_child(&mut arena, head, "b1c3");
+        let f3_f6 = expand_child(&mut arena, knight_f3, "g8f6");
+        let c3_f6 = expand_child(&mut arena, knight_c3, "g8f6");
+        let transposition = child_of(&arena, f3_f6, "b1c3");
+        assert_eq!(transposition, child_of(&arena, c3_f6, "g1f3"));
+        assert_eq!(arena.node(transposition).parents.len(), 2);
+        let playout = Playout {
+            outcome: GameOutcome::WhiteWins,
+            moves: Vec::new(),
+        };
+        let path = [head, knight_f3, f3_f6, transposition];
+        arena.backpropagate(&path, &playout, &tree.params);
+        for node_id in path {
+            assert_eq!(arena.node(node_id).visits(), 1);
+        }
+        // The other parent of the transposition did not choose the moves leading to it
+        assert_eq!(arena.node(c3_f6).visits(), 0);
+        assert_eq!(arena.node(knight_c3).visits(), 0);
+    }
+
+    #[test]
+    fn tree_parallel_search_shares_the_tree() {
+        let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(f32::MAX);
+        tree.set_max_iterations(Some(300));
+        tree.set_early_stop(false);
+        tree.set_n_threads(3);
+        tree.set_parallel_mode(ParallelMode::Tree);
+        tree.set_virtual_loss(3);
+        let n_iterations = tree._populate();
+        assert!(n_iterations >= 300);
+        // Each iteration of any worker is credited to the head once
+        assert_eq!(tree.node(tree.head).visits(), n_iterations);
+        // All virtual losses were taken back
+        assert!(tree
+            .arena
+            .nodes
+            .iter()
+            .all(|x| x.virtual_loss.load(Ordering::Relaxed) == 0));
+        let chess_move = tree._yield_best_move(chess::Color::White);
+        assert!(chess::Board::default().legal(chess_move));
+    }
+
+    #[test]
+    fn single_thread_search_repeats_itself_for_a_seed() {
+        let search = || {
+            let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+            tree.set_max_search_time(f32::MAX);
+            tree.set_max_iterations(Some(300));
+            tree.set_early_stop(false);
+            tree.set_seed(7);
+            let n_iterations = tree._populate();
+            let children_visits: Vec<usize> = tree
+                .node(tree.head)
+                .children
+                .iter()
+                .map(|x| tree.node(*x).visits())
+                .collect();
+            let chess_move = tree._yield_best_move(chess::Color::White);
+            (n_iterations, children_visits, chess_move)
+        };
+        assert_eq!(search(), search());
+    }
+
+    #[test]
+    fn played_child_is_carried_over_as_the_head() {
+        let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        tree.set_max_search_time(f32::MAX);
+        tree.set_max_iterations(Some(500));
+        tree.set_early_stop(false);
+        tree._populate();
+        let most_visited = tree
+            .arena
+            .best_child(tree.head, MoveSelection::MaxVisits)
+            .unwrap();
+        let expected_move = tree.arena.get_move_diff(tree.head, most_visited);
+        let visits = tree.node(most_visited).visits();
+        let n_children = tree.node(most_visited).children.len();
+        assert!(n_children > 0);
+        let chess_move = tree._yield_best_move(chess::Color::White);
+        assert_eq!(chess_move, expected_move);
+        // The subtree and the statistics of the played move are kept
+        assert_eq!(tree.node(tree.head).visits(), visits);
+        assert_eq!(tree.node(tree.head).children.len(), n_children);
+        assert_eq!(tree.carried_visits(), visits);
+        assert!(tree.carried_visits() > 0);
+    }
+
+    #[test]
+    fn amaf_credits_the_later_moves_of_each_side_once() {
+        let (mut arena, head) = expanded_arena(chess::Board::default());
+        let king_pawn = child_of(&arena, head, "e2e4");
+        let children = child_boards(&arena.node(king_pawn).board, &UniformPolicy);
+        arena.add_children(king_pawn, children.into_iter());
+        // White plays Nf3 twice, Black's knight goes back as well
+        let playout = Playout {
+            outcome: GameOutcome::WhiteWins,
+            moves: ["e7e5", "g1f3", "b8c6", "f3g1", "c6b8", "g1f3"]
+                .iter()
+                .map(|x| chess::ChessMove::from_str(x).unwrap())
+                .collect(),
+        };
+        arena.backpropagate_amaf(&[head, king_pawn], &playout);
+        // Moves of the edges holding AMAF statistics, with their AMAF visits and wins
+        let credited = |parent: NodeId| {
+            let board = arena.node(parent).board;
+            chess::MoveGen::new_legal(&board)
+                .zip(&arena.node(parent).amaf)
+                .filter(|(_, amaf)| amaf.visits() > 0)
+                .map(|(chess_move, amaf)| {
+                    (
+                        chess_move.to_string(),
+                        amaf.visits(),
+                        amaf.wins(board.side_to_move()),
+                    )
+                })
+                .collect::<Vec<(String, usize, f32)>>()
+        };
+        let mut white_moves = credited(head);
+        white_moves.sort_by(|x, y| x.0.cmp(&y.0));
+        let expected = [("e2e4", 1, 1_f32), ("g1f3", 1, 1_f32)];
+        assert_eq!(white_moves, expected.map(|(x, y, z)| (x.to_string(), y, z)));
+        let mut black_moves = credited(king_pawn);
+        black_moves.sort_by(|x, y| x.0.cmp(&y.0));
+        let expected = [("b8c6", 1, 0_f32), ("e7e5", 1, 0_f32)];
+        assert_eq!(black_moves, expected.map(|(x, y, z)| (x.to_string(), y, z)));
+    }
+
+    #[test]
+    fn amaf_statistics_of_transpositions_stay_on_their_edges() {
+        // 1. Nf3 Nf6 2. Nc3 and 1. Nc3 Nf6 2. Nf3 transpose into the same node
+        let mut tree = Tree::new(chess::Board::default(), MAX_SEARCH_DEPTH);
+        tree.set_rave(Some(RaveSchedule::default()));
+        let (mut arena, head) = expanded_arena(chess::Board::default());
+        for (parent, uci) in [(head, "g1f3"), (head, "b1c3")] {
+            let child = child_of(&arena, parent, uci);
+            let children = child_boards(&arena.node(child).board, &UniformPolicy);
+            arena.add_children(child, children.into_iter());
+            let grandchild = child_of(&arena, child, "g8f6");
+            let children = child_boards(&arena.node(grandchild).board, &UniformPolicy);
+            arena.add_children(grandchild, children.into_iter());
+        }
+        let f3_f6 = child_of(&arena, child_of(&arena, head, "g1f3"), "g8f6");
+        let c3_f6 = child_of(&arena, child_of(&arena, head, "b1c3"), "g8f6");
+        let transposition = child_of(&arena, f3_f6, "b1c3");
+        assert_eq!(transposition, child_of(&arena, c3_f6, "g1f3"));
+        // AMAF statistics of the edge from `parent` along the UCI move `uci`
+        let edge = |parent: NodeId, uci: &str| {
+            let chess_move = chess::ChessMove::from_str(uci).unwrap();
+            let board = arena.node(parent).board;
+            let i = chess::MoveGen::new_legal(&board)
+                .position(|x| x == chess_move)
+                .unwrap();
+            &arena.node(parent).amaf[i]
+        };
+        // Nf3 is played later on in a playout from 1. Nc3 Nf6
+        let playout = Playout {
+            outcome: GameOutcome::WhiteWins,
+            moves: ["g1f3"]
+                .iter()
+                .map(|x| chess::ChessMove::from_str(x).unwrap())
+                .collect(),
+        };
+        let path = [head, child_of(&arena, head, "b1c3"), c3_f6];
+        arena.backpropagate(&path, &playout, &tree.params);
+        assert_eq!(edge(c3_f6, "g1f3").visits(), 1);
+        // The other parent of the transposition never played Nc3 after 1. Nf3 Nf6
+        assert_eq!(edge(f3_f6, "b1c3").visits(), 0);
+        // The shared node is scored with the AMAF win of the edge it is reached along only
+        set_stats(&arena, transposition, 10, 5_f32);
+        let score = |amaf| arena.edge_score(transposition, Some(amaf), &tree.params);
+        assert!(score(edge(c3_f6, "g1f3")) > score(edge(f3_f6, "b1c3")));
+    }
 }
@@ -0,0 +1,756 @@
+use crate::api::{self, ApiHandler};
+use crate::chat::GameChat;
+use crate::game::{BotAction, BotGame, SyncStatus};
+use crate::matchmaking::{cancel_stale_challenges, matchmaking_loop};
+use crate::mcts::search::Engine;
+use crate::utils::backoff::Backoff;
+use lichess_api::error::Error as LichessError;
+use lichess_api::model::board::stream::events::{Event, GameEventInfo};
+use lichess_api::model::board::stream::game::GameState;
+use lichess_api::model::bot::chat::PostRequest as ChatPostRequest;
+use lichess_api::model::bot::stream::game::Event as BotGameEvent;
+use lichess_api::model::challenges::decline::{PostRequest as DeclinePostRequest, Reason};
+use lichess_api::model::Room;
+use log::{debug, info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::{Stream, StreamExt};
+
+// Consecutive failed connections after which a game stream is given up
+const MAX_GAME_STREAM_ATTEMPTS: u32 = 10;
+// Maximal number of ongoing games fetched after (re)connecting
+const MAX_ONGOING_GAMES: u8 = 50;
+// Time between two checks for running games while shutting down
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Time the resigned games have to close their streams before the bot exits
+const RESIGN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Drives the Lichess bot account with the engine `E`, one `BotGame<E>` per started game.
+pub struct BotRunner<E: Engine<E>> {
+    api_handle: Arc<ApiHandler<E>>,
+}
+
+impl<E: Engine<E> + 'static> BotRunner<E> {
+    pub fn new() -> Result<BotRunner<E>, Box<dyn std::error::Error>> {
+        // Load the configurations and build the API client
+        let api_handle = Arc::new(api::ApiHandler::<E>::new()?);
+        Ok(BotRunner { api_handle })
+    }
+
+    pub fn api_handle(&self) -> Arc<ApiHandler<E>> {
+        self.api_handle.clone()
+    }
+
+    /// Plays until SIGINT or SIGTERM, then drains the running games as set by the shutdown
+    /// policy.
+    pub async fn run(&self) {
+        if self.api_handle.bot_config.matchmaking.enabled {
+            tokio::spawn(matchmaking_loop(self.api_handle.clone()));
+        }
+        // Launch event listening loop, kept open while draining to decline new challenges
+        tokio::select! {
+            _ = streaming_loop(self.api_handle.clone()) => {}
+            _ = async {
+                shutdown_signal().await;
+                shutdown(&self.api_handle).await;
+            } => {}
+        }
+        info!("Shut down");
+    }
+}
+
+/// Resolves on SIGINT or, on Unix, SIGTERM (e.g. `docker stop`).
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(error) => {
+                warn!("Unable to listen for SIGTERM: {error}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Stops taking new games and ends the running ones: they are played on for up to
+/// `drain_timeout` seconds if `finish_games` is set, the remaining ones and the games waiting for
+/// a free slot are resigned. A second signal resigns right away.
+async fn shutdown<E: Engine<E>>(api_handle: &ApiHandler<E>) {
+    let policy = &api_handle.bot_config.shutdown;
+    api_handle.start_shutdown();
+    info!(
+        "Shutting down with {} running games",
+        api_handle.running_games()
+    );
+    cancel_stale_challenges(api_handle, Duration::ZERO).await;
+    if policy.finish_games {
+        let timeout = Duration::from_secs(policy.drain_timeout);
+        tokio::select! {
+            result = tokio::time::timeout(timeout, games_ended(api_handle)) => {
+                if result.is_err() {
+                    info!("Running games did not end within {}s", policy.drain_timeout);
+                }
+            }
+            _ = shutdown_signal() => info!("Second shutdown signal received"),
+        }
+    }
+    let running_games = api_handle
+        .game_tasks
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, task)| !task.is_finished())
+        .map(|(game_id, _)| game_id.clone())
+        .collect::<Vec<String>>();
+    // Searching on is pointless, the pool would wait for the searches when it is dropped
+    api_handle.stop_searches();
+    let waiting_games = std::mem::take(&mut *api_handle.waiting_games.lock().unwrap());
+    let waiting_games = waiting_games.into_iter().map(|x| x.game_id);
+    for game_id in running_games.into_iter().chain(waiting_games) {
+        info!("Resigning game {game_id} to shut down");
+        resign(api_handle, &game_id).await;
+    }
+    // The game streams end with the final state of the resigned games, the tasks still
+    // running are dropped along with the runtime
+    let _ = tokio::time::timeout(RESIGN_GRACE_PERIOD, games_ended(api_handle)).await;
+}
+
+async fn games_ended<E: Engine<E>>(api_handle: &ApiHandler<E>) {
+    while api_handle.running_games() > 0 {
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+/// How a stream of events stopped.
+enum StreamEnd {
+    /// The game is over, its stream must not be reopened
+    GameOver,
+    /// Connection lost or closed by Lichess
+    Disconnected,
+}
+
+/// Keeps the incoming events stream open, reconnecting with backoff whenever it fails.
+async fn streaming_loop<E: Engine<E> + 'static>(api_handle: Arc<ApiHandler<E>>) -> () {
+    let mut backoff = Backoff::default();
+    loop {
+        let event_stream = match api_handle.get_event_stream().await {
+            Ok(event_stream) => event_stream,
+            Err(error) => {
+                warn!(
+                    "Unable to open the event stream (attempt {}): {error}",
+                    backoff.attempt() + 1
+                );
+                backoff.wait().await;
+                continue;
+            }
+        };
+        debug!("Event stream connected");
+        backoff.reset();
+        // Games may have started, progressed or finished while disconnected
+        resume_ongoing_games(api_handle.clone()).await;
+        event_stream_loop(api_handle.clone(), event_stream).await;
+        warn!("Event stream disconnected, reconnecting");
+        backoff.wait().await;
+    }
+}
+
+async fn event_stream_loop<E: Engine<E> + 'static>(
+    api_handle: Arc<ApiHandler<E>>,
+    mut event_stream: impl Stream<Item = Result<Event, LichessError>> + Unpin,
+) {
+    debug!("Printing incoming events ... \n");
+    while let Some(item) = event_stream.next().await {
+        let event_api_handle = api_handle.clone();
+        // let rt_handle = rt.clone();
+        debug!("Received streaming loop event: {:?}", item);
+        match item {
+            Ok(Event::Challenge {
+                challenge: ref json,
+            }) => {
+                // Challenges sent by the bot show up in the stream as well
+                if json
+                    .base
+                    .challenger
+                    .user
+                    .name
+                    .eq_ignore_ascii_case(&event_api_handle.user)
+                {
+                    continue;
+                }
+                let challenge_id = json.base.id.to_string();
+                // Accepted outgoing challenges are about to start a game
+                let running_games = event_api_handle.active_games()
+                    + event_api_handle.matchmaker.lock().unwrap().n_pending();
+                let reason = if event_api_handle.is_shutting_down() {
+                    Some(Reason::Later)
+                } else {
+                    event_api_handle
+                        .bot_config
+                        .decline_reason(json, running_games)
+                };
+                if let Some(reason) = reason {
+                    debug!("Declining challenge {challenge_id}: {reason:?}");
+                    let decline_request = DeclinePostRequest::new(challenge_id, reason);
+                    if let Err(error) = event_api_handle
+                        .lichess_api
+                        .decline_challenge(decline_request)
+                        .await
+                    {
+                        warn!("Unable to decline challenge: {error}");
+                    }
+                    continue;
+                }
+                let accept_request =
+                    lichess_api::model::challenges::accept::PostRequest::new(challenge_id);
+                let _accept_challenge_res = event_api_handle
+                    .lichess_api
+                    .accept_challenge(accept_request)
+                    .await;
+                debug!("Challenge accepted");
+            }
+            Ok(Event::ChallengeDeclined { challenge: json }) => {
+                event_api_handle
+                    .matchmaker
+                    .lock()
+                    .unwrap()
+                    .on_challenge_declined(&json.base.id);
+            }
+            Ok(Event::ChallengeCanceled { challenge: json }) => {
+                event_api_handle
+                    .matchmaker
+                    .lock()
+                    .unwrap()
+                    .on_challenge_canceled(&json.base.id);
+            }
+            Ok(Event::GameStart { game }) => {
+                event_api_handle
+                    .matchmaker
+                    .lock()
+                    .unwrap()
+                    .on_game_start(&game.game_id);
+                spawn_game(event_api_handle.clone(), game);
+            }
+            Ok(Event::GameFinish { game: info }) => {
+                // The game's task forgets the game once its stream ends
+                debug!("Game {} finished", info.game_id);
+            }
+            // Unknown or malformed events do not affect the connection
+            Err(LichessError::Json(error)) => {
+                warn!("Skipping unreadable event: {error}");
+            }
+            Err(error) => {
+                warn!("Event stream failed: {error}");
+                return;
+            }
+        }
+    }
+}
+
+/// Resumes the games the bot is part of, e.g. after a restart or a lost connection.
+async fn resume_ongoing_games<E: Engine<E> + 'static>(api_handle: Arc<ApiHandler<E>>) {
+    let request = lichess_api::model::games::ongoing::GetRequest::new(MAX_ONGOING_GAMES);
+    let ongoing_games = match api_handle.lichess_api.get_my_ongoing_games(request).await {
+        Ok(ongoing_games) => ongoing_games.now_playing,
+        Err(error) => {
+            warn!("Unable to fetch ongoing games: {error}");
+            return;
+        }
+    };
+    // Forget the games whose task ended, the running ones keep their engine and are not respawned
+    {
+        let game_tasks = api_handle.game_tasks.lock().unwrap();
+        api_handle
+            .game_handles
+            .lock()
+            .unwrap()
+            .retain(|game_id, _| game_tasks.get(game_id).is_some_and(|x| !x.is_finished()));
+    }
+    // Games beyond `max_concurrent_games` wait for a free slot like started ones
+    for game in ongoing_games {
+        debug!("Resuming game {}", game.game_id);
+        spawn_game(api_handle.clone(), game);
+    }
+}
+
+/// Plays `game` in a task of its own, unless its task is still running. While
+/// `max_concurrent_games` games are running, the game waits for one of them to end.
+///
+/// The game is played by an inner task, so that a panic ends that game only and is logged by
+/// the supervising task, which then forgets the game and starts the next waiting one.
+fn spawn_game<E: Engine<E> + 'static>(api_handle: Arc<ApiHandler<E>>, game: GameEventInfo) {
+    let mut game_tasks = api_handle.game_tasks.lock().unwrap();
+    game_tasks.retain(|_, task| !task.is_finished());
+    if game_tasks.contains_key(&game.game_id) {
+        debug!("Game {} is already being played", game.game_id);
+        return;
+    }
+    if game_tasks.len() >= api_handle.bot_config.max_concurrent_games {
+        let mut waiting_games = api_handle.waiting_games.lock().unwrap();
+        if !waiting_games.iter().any(|x| x.game_id == game.game_id) {
+            info!("Game {} waits for a running game to end", game.game_id);
+            waiting_games.push_back(game);
+        }
+        return;
+    }
+    let game_id = game.game_id.clone();
+    api_handle
+        .game_opponents
+        .lock()
+        .unwrap()
+        .insert(game_id.clone(), game.opponent.username.clone());
+    let task_api_handle = api_handle.clone();
+    let supervisor = tokio::spawn(async move {
+        let game_id = game.game_id.clone();
+        if let Err(error) = tokio::spawn(play_game(task_api_handle.clone(), game)).await {
+            warn!("Game {game_id} task failed: {error}");
+        }
+        task_api_handle
+            .game_handles
+            .lock()
+            .unwrap()
+            .remove(&game_id);
+        task_api_handle
+            .game_opponents
+            .lock()
+            .unwrap()
+            .remove(&game_id);
+        // Free the slot of the game before handing it over
+        task_api_handle.game_tasks.lock().unwrap().remove(&game_id);
+        debug!("Game {game_id} task ended");
+        spawn_waiting_game(task_api_handle);
+    });
+    game_tasks.insert(game_id, supervisor);
+}
+
+/// Plays the game waiting the longest for a free slot, unless the bot is shutting down.
+fn spawn_waiting_game<E: Engine<E> + 'static>(api_handle: Arc<ApiHandler<E>>) {
+    if api_handle.is_shutting_down() {
+        return;
+    }
+    let waiting_game = api_handle.waiting_games.lock().unwrap().pop_front();
+    if let Some(game) = waiting_game {
+        spawn_game(api_handle, game);
+    }
+}
+
+/// Plays a game until it is over, keeping the engine of a game which is already tracked.
+async fn play_game<E: Engine<E> + 'static>(api_handle: Arc<ApiHandler<E>>, game: GameEventInfo) {
+    api_handle
+        .game_handles
+        .lock()
+        .unwrap()
+        .entry(game.game_id.clone())
+        .or_insert_with(|| {
+            Arc::new(Mutex::new(BotGame::new_from_challenge(
+                &game,
+                &api_handle.search_config,
+                api_handle.stop_flag.clone(),
+            )))
+        });
+    bot_game_stream(api_handle.clone(), game.game_id.clone()).await;
+}
+
+/// Keeps the stream of a game open until the game is over, reconnecting with backoff.
+async fn bot_game_stream<E: Engine<E> + 'static>(
+    lichess_api: Arc<ApiHandler<E>>,
+    id: String,
+) -> () {
+    let mut backoff = Backoff::default();
+    let mut stream_state = GameStreamState::default();
+    loop {
+        let request = lichess_api::model::bot::stream::game::GetRequest::new(id.as_str());
+        match lichess_api
+            .lichess_api
+            .bot_stream_board_state(request)
+            .await
+        {
+            Ok(events_stream) => {
+                backoff.reset();
+                match game_stream_loop(lichess_api.clone(), &id, events_stream, &mut stream_state)
+                    .await
+                {
+                    StreamEnd::GameOver => return,
+                    StreamEnd::Disconnected => warn!("Game {id} stream disconnected"),
+                }
+            }
+            Err(error) => {
+                warn!(
+                    "Unable to open game {id} stream (attempt {}): {error}",
+                    backoff.attempt() + 1
+                );
+                if backoff.attempt() + 1 >= MAX_GAME_STREAM_ATTEMPTS {
+                    warn!("Giving up game {id}");
+                    return;
+                }
+            }
+        }
+        backoff.wait().await;
+    }
+}
+
+fn game_handle<E: Engine<E>>(api_handle: &ApiHandler<E>, game_id: &str) -> Arc<Mutex<BotGame<E>>> {
+    let games_hashmap = api_handle.game_handles.lock().unwrap();
+    games_hashmap
+        .get(game_id)
+        .unwrap_or_else(|| panic!("Game id {} not found in {:?}", game_id, games_hashmap))
+        .clone()
+}
+
+/// Action taken by the bot when a deadline of its game passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameTimer {
+    /// The opponent did not play its first move
+    Abort,
+    /// The opponent left the game for long enough
+    ClaimVictory,
+}
+
+/// State of a game stream carried from one event to the next, and over reconnections.
+#[derive(Debug, Default)]
+struct GameStreamState {
+    timer: Option<(Instant, GameTimer)>,
+    // Number of moves when the bot accepted a takeback, until Lichess rewinds the game
+    takeback_from: Option<usize>,
+    chat: GameChat,
+}
+
+async fn game_stream_loop<E: Engine<E> + 'static>(
+    lichess_api: Arc<ApiHandler<E>>,
+    id: &str,
+    mut events_stream: impl Stream<Item = Result<BotGameEvent, LichessError>> + Unpin,
+    stream_state: &mut GameStreamState,
+) -> StreamEnd {
+    let game_id = String::from(id);
+    let policy = &lichess_api.bot_config.game;
+    loop {
+        let event = match stream_state.timer {
+            Some((deadline, timer)) => {
+                match tokio::time::timeout_at(deadline, events_stream.next()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        // The final game state follows if Lichess ends the game
+                        stream_state.timer = None;
+                        on_game_timer(&lichess_api, &game_id, timer).await;
+                        continue;
+                    }
+                }
+            }
+            None => events_stream.next().await,
+        };
+        let Some(event) = event else { break };
+        debug!("Received game loop event: {:?}", event);
+        match event {
+            Ok(BotGameEvent::GameFull { game_full }) => {
+                game_handle(&lichess_api, &game_id)
+                    .lock()
+                    .unwrap()
+                    .set_initial_fen(game_full.initial_fen.as_deref());
+                let Some(game_state) = game_full.state else {
+                    warn!("Resigning game {game_id}, its full state has no game state");
+                    resign(&lichess_api, &game_id).await;
+                    return StreamEnd::GameOver;
+                };
+                let opponent = if game_full.white.name.eq_ignore_ascii_case(&lichess_api.user) {
+                    &game_full.black.name
+                } else {
+                    &game_full.white.name
+                };
+                let n_moves = game_state.moves.split_whitespace().count();
+                let greetings = stream_state.chat.greetings(
+                    &lichess_api.bot_config.chat,
+                    &lichess_api.user,
+                    opponent,
+                    n_moves,
+                );
+                for (room, message) in greetings {
+                    send_chat(&lichess_api, &game_id, room, &message).await;
+                }
+                if let Some(stream_end) =
+                    on_game_state(&lichess_api, &game_id, &game_state, stream_state).await
+                {
+                    return stream_end;
+                }
+            }
+            Ok(BotGameEvent::GameState { game_state, .. }) => {
+                if let Some(stream_end) =
+                    on_game_state(&lichess_api, &game_id, &game_state, stream_state).await
+                {
+                    return stream_end;
+                }
+            }
+            Ok(BotGameEvent::ChatLine { chat_line }) => {
+                if lichess_api.user.eq_ignore_ascii_case(&chat_line.username) {
+                    continue;
+                }
+                let search_info = game_handle(&lichess_api, &game_id)
+                    .lock()
+                    .unwrap()
+                    .last_search_info();
+                if let Some(reply) = stream_state.chat.reply(
+                    &lichess_api.bot_config.chat,
+                    &lichess_api.user,
+                    &chat_line.text,
+                    &chat_line.room,
+                    search_info.as_ref(),
+                ) {
+                    send_chat(&lichess_api, &game_id, chat_line.room, &reply).await;
+                }
+            }
+            Ok(BotGameEvent::OpponentGone { opponent_gone }) => {
+                match (opponent_gone.gone, opponent_gone.claim_win_in_seconds) {
+                    (true, Some(seconds)) if policy.claim_victory => {
+                        debug!("Opponent gone in game {game_id}, claiming victory in {seconds}s");
+                        let deadline = Instant::now() + Duration::from_secs(seconds.into());
+                        stream_state.timer = Some((deadline, GameTimer::ClaimVictory));
+                    }
+                    (false, _)
+                        if matches!(stream_state.timer, Some((_, GameTimer::ClaimVictory))) =>
+                    {
+                        debug!("Opponent back in game {game_id}");
+                        stream_state.timer = None;
+                    }
+                    _ => {}
+                }
+            }
+            // Unknown or malformed events do not affect the connection
+            Err(LichessError::Json(error)) => {
+                warn!("Skipping unreadable event of game {game_id}: {error}");
+            }
+            Err(error) => {
+                warn!("Game {game_id} stream failed: {error}");
+                return StreamEnd::Disconnected;
+            }
+        }
+    }
+    // Lichess closed the stream without a final game state
+    StreamEnd::Disconnected
+}
+
+/// Answers a game state: ends the stream once the game is over, answers the opponent's offers
+/// and plays the bot's move.
+async fn on_game_state<E: Engine<E> + 'static>(
+    lichess_api: &Arc<ApiHandler<E>>,
+    game_id: &str,
+    game_state: &GameState,
+    stream_state: &mut GameStreamState,
+) -> Option<StreamEnd> {
+    // Games are "created" until both players moved
+    if !matches!(game_state.status.as_str(), "created" | "started") {
+        info!(
+            "Game {game_id} over: {}, winner {:?}",
+            game_state.status, game_state.winner
+        );
+        let goodbyes = stream_state.chat.goodbyes(
+            &lichess_api.bot_config.chat,
+            &lichess_api.user,
+            &game_state.status,
+        );
+        for (room, message) in goodbyes {
+            send_chat(lichess_api, game_id, room, &message).await;
+        }
+        return Some(StreamEnd::GameOver);
+    }
+    let n_moves = game_state
+        .moves
+        .split(' ')
+        .filter(|x| !x.is_empty())
+        .count();
+    // Lichess took the moves back once the game is shorter than when the takeback was accepted
+    let rewind = stream_state.takeback_from.is_some_and(|x| n_moves < x);
+    if rewind {
+        stream_state.takeback_from = None;
+    }
+    if opponent_offers(
+        lichess_api,
+        game_id,
+        game_state.wtakeback,
+        game_state.btakeback,
+    ) {
+        let accept = lichess_api.bot_config.game.accept_takebacks;
+        if answer_takeback(lichess_api, game_id, accept).await && accept {
+            // The rewound game state follows
+            stream_state.takeback_from = Some(n_moves);
+            return None;
+        }
+    }
+    if opponent_offers(lichess_api, game_id, game_state.wdraw, game_state.bdraw)
+        && game_handle(lichess_api, game_id)
+            .lock()
+            .unwrap()
+            .accepts_draw()
+        && accept_draw(lichess_api, game_id).await
+    {
+        return None;
+    }
+    let Some(bots_turn) = sync_game(lichess_api, game_id, game_state, rewind) else {
+        info!("Resigning game {game_id}, its moves can not be replayed");
+        resign(lichess_api, game_id).await;
+        return None;
+    };
+    // Lichess lets players abort until both of them moved
+    let awaits_first_move = !bots_turn && n_moves < 2;
+    match stream_state.timer {
+        None if awaits_first_move => {
+            let timeout = Duration::from_secs(lichess_api.bot_config.game.abort_timeout);
+            stream_state.timer = Some((Instant::now() + timeout, GameTimer::Abort));
+        }
+        Some((_, GameTimer::Abort)) if !awaits_first_move => stream_state.timer = None,
+        _ => {}
+    }
+    if bots_turn {
+        play_move(lichess_api, game_id).await;
+    } else {
+        debug!("Waiting for opponent's move");
+    }
+    None
+}
+
+async fn on_game_timer<E: Engine<E>>(api_handle: &ApiHandler<E>, game_id: &str, timer: GameTimer) {
+    let result = match timer {
+        GameTimer::Abort => {
+            info!("Aborting game {game_id}, the opponent did not move");
+            let request = lichess_api::model::bot::abort::PostRequest::new(game_id);
+            api_handle.lichess_api.bot_abort_game(request).await
+        }
+        GameTimer::ClaimVictory => {
+            info!("Claiming victory in game {game_id}, the opponent left");
+            let path = format!("game/{game_id}/claim-victory");
+            api_handle.bot_post(&path).await
+        }
+    };
+    if let Err(error) = result {
+        warn!("Unable to end game {game_id} ({timer:?}): {error}");
+    }
+}
+
+/// Whether the opponent's flag among White's and Black's ones is set.
+fn opponent_offers<E: Engine<E>>(
+    api_handle: &ApiHandler<E>,
+    game_id: &str,
+    white_offers: Option<bool>,
+    black_offers: Option<bool>,
+) -> bool {
+    let offers = match game_handle(api_handle, game_id).lock().unwrap().bot_is {
+        chess::Color::White => black_offers,
+        chess::Color::Black => white_offers,
+    };
+    offers.unwrap_or(false)
+}
+
+/// Answers the opponent's takeback proposal, returns whether Lichess took the answer.
+async fn answer_takeback<E: Engine<E>>(
+    api_handle: &ApiHandler<E>,
+    game_id: &str,
+    accept: bool,
+) -> bool {
+    debug!("Answering takeback proposal in game {game_id}: accept {accept}");
+    let answer = if accept { "yes" } else { "no" };
+    let path = format!("game/{game_id}/takeback/{answer}");
+    match api_handle.bot_post(&path).await {
+        Ok(answered) => answered,
+        Err(error) => {
+            warn!("Unable to answer takeback proposal in game {game_id}: {error}");
+            false
+        }
+    }
+}
+
+/// Brings the engine's board up to date with the moves played online and hands it the clock.
+///
+/// Returns whether the bot is to move, `None` if the game can not be played on. The game is locked
+/// in this synchronous function only, its guard never lives across an await point.
+fn sync_game<E: Engine<E>>(
+    api_handle: &ApiHandler<E>,
+    game_id: &str,
+    game_state: &GameState,
+    rewind: bool,
+) -> Option<bool> {
+    let game = game_handle(api_handle, game_id);
+    let mut game_guard = game.lock().unwrap();
+    match game_guard.sync_moves(&game_state.moves, rewind) {
+        SyncStatus::InSync => {}
+        SyncStatus::Lagging => return Some(false),
+        SyncStatus::Unplayable => return None,
+    }
+    let side_to_play = game_guard.botgame_side_to_move();
+    debug!(
+        "GameState: bot_is {:?}, side to  play {:?}, moves {}, game fen {}",
+        game_guard.bot_is,
+        side_to_play,
+        game_state.moves,
+        game_guard.get_fen()
+    );
+    if game_guard.bot_is != side_to_play {
+        return Some(false);
+    }
+    game_guard.update_clock(game_state);
+    Some(true)
+}
+
+/// Searches the bot's next move off the async runtime and sends it to Lichess.
+async fn play_move<E: Engine<E> + 'static>(api_handle: &Arc<ApiHandler<E>>, game_id: &str) {
+    let game = game_handle(api_handle, game_id);
+    match crate::game::yield_next_move(game, api_handle.clone()).await {
+        BotAction::Move {
+            chess_move,
+            offer_draw,
+        } => {
+            debug!("BotGameStream: move {chess_move}; offer draw: {offer_draw}");
+            let request = lichess_api::model::bot::r#move::PostRequest::new(
+                game_id,
+                chess_move.as_str(),
+                offer_draw,
+            );
+            if let Err(error) = api_handle.lichess_api.bot_make_move(request).await {
+                warn!("Unable to play move {chess_move} in game {game_id}: {error}");
+            }
+        }
+        BotAction::Resign => {
+            info!("Resigning game {game_id}");
+            resign(api_handle, game_id).await;
+        }
+    }
+}
+
+async fn resign<E: Engine<E>>(api_handle: &ApiHandler<E>, game_id: &str) {
+    let request = lichess_api::model::bot::resign::PostRequest::new(game_id);
+    if let Err(error) = api_handle.lichess_api.bot_resign_game(request).await {
+        warn!("Unable to resign game {game_id}: {error}");
+    }
+}
+
+async fn send_chat<E: Engine<E>>(
+    api_handle: &ApiHandler<E>,
+    game_id: &str,
+    room: Room,
+    text: &str,
+) {
+    debug!("Game {game_id} chat ({room:?}): {text}");
+    let request = ChatPostRequest::new(game_id, room, text);
+    if let Err(error) = api_handle.lichess_api.bot_write_in_chat(request).await {
+        warn!("Unable to write in game {game_id} chat: {error}");
+    }
+}
+
+/// Accepts the opponent's draw offer, returns whether Lichess took the answer.
+async fn accept_draw<E: Engine<E>>(api_handle: &ApiHandler<E>, game_id: &str) -> bool {
+    info!("Accepting draw offer in game {game_id}");
+    let request = lichess_api::model::bot::draw::PostRequest::new(game_id, true);
+    match api_handle.lichess_api.bot_draw_game(request).await {
+        Ok(accepted) => accepted,
+        Err(error) => {
+            warn!("Unable to accept draw offer in game {game_id}: {error}");
+            false
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use crate::mcts::config::SearchConfig;
+use crate::mcts::time_manager::{GameClock, TimeManager};
+use crate::mcts::tree::{SearchInfo, Tree};
+use crate::utils::misc::board_from_fen;
+use crate::utils::parse_args::get_search_config;
+use chess::{Board, ChessMove, Color};
+use core::fmt;
+use log::debug;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const ENGINE_NAME: &str = "Brocciu";
+const ENGINE_AUTHOR: &str = "Pierre Reboud";
+// Time kept per move for the engine's own latency, in milliseconds
+const MOVE_OVERHEAD: u64 = 20;
+// Upper bound of the `Threads` option
+const MAX_THREADS: usize = 256;
+// Time between two checks for `stop` once an infinite search is over
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UciCommand {
+    Uci,
+    IsReady,
+    UciNewGame,
+    SetOption { name: String, value: Option<String> },
+    Position { board: Board, moves: Vec<ChessMove> },
+    Go(GoParams),
+    Stop,
+    Quit,
+    Unknown(String),
+}
+
+/// Search limits of a `go` command, times in milliseconds.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GoParams {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    /// Search time in seconds allotted to `side_to_move`, `None` if `go` carries no time limit.
+    pub fn search_time(&self, side_to_move: Color, time_manager: &TimeManager) -> Option<f32> {
+        if self.infinite {
+            return Some(f32::INFINITY);
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime as f32 / 1000_f32);
+        }
+        let time = match side_to_move {
+            Color::White => self.wtime,
+            Color::Black => self.btime,
+        };
+        // Without a clock for the side to move, the caller's default applies
+        time?;
+        let clock = GameClock {
+            wtime: self.wtime.unwrap_or(0),
+            btime: self.btime.unwrap_or(0),
+            winc: self.winc.unwrap_or(0),
+            binc: self.binc.unwrap_or(0),
+        };
+        // The GUI runs locally, the move overhead only covers the engine's own latency
+        let time_manager = TimeManager {
+            move_overhead: MOVE_OVERHEAD,
+            moves_to_go: self.movestogo.unwrap_or(time_manager.moves_to_go),
+            max_move_time: f32::INFINITY,
+            ..time_manager.clone()
+        };
+        Some(time_manager.move_time(&clock, side_to_move))
+    }
+}
+
+pub fn parse_command(line: &str) -> Result<UciCommand, UciParseError> {
+    let mut tokens = line.split_whitespace();
+    let command = match tokens.next() {
+        Some("uci") => UciCommand::Uci,
+        Some("isready") => UciCommand::IsReady,
+        Some("ucinewgame") => UciCommand::UciNewGame,
+        Some("setoption") => parse_setoption(tokens.collect())?,
+        Some("position") => parse_position(tokens.collect())?,
+        Some("go") => UciCommand::Go(parse_go(tokens.collect())?),
+        Some("stop") => UciCommand::Stop,
+        Some("quit") => UciCommand::Quit,
+        _ => UciCommand::Unknown(line.to_string()),
+    };
+    Ok(command)
+}
+
+fn parse_setoption(tokens: Vec<&str>) -> Result<UciCommand, UciParseError> {
+    // Option names and values may contain spaces
+    let value_index = tokens.iter().position(|x| *x == "value");
+    let (name, value) = match value_index {
+        Some(i) => (&tokens[..i], Some(tokens[i + 1..].join(" "))),
+        None => (&tokens[..], None),
+    };
+    match name.split_first() {
+        Some((&"name", name)) if !name.is_empty() => Ok(UciCommand::SetOption {
+            name: name.join(" "),
+            value,
+        }),
+        _ => Err(UciParseError::new("setoption expects a name".to_string())),
+    }
+}
+
+fn parse_position(tokens: Vec<&str>) -> Result<UciCommand, UciParseError> {
+    // Split the position description from the move list
+    let moves_index = tokens.iter().position(|x| *x == "moves");
+    let (position, moves) = match moves_index {
+        Some(i) => (&tokens[..i], &tokens[i + 1..]),
+        None => (&tokens[..], &tokens[tokens.len()..]),
+    };
+    let board = match position.split_first() {
+        Some((&"startpos", _)) => Board::default(),
+        Some((&"fen", fen)) => board_from_fen(&fen.join(" "))
+            .ok_or_else(|| UciParseError::new(format!("invalid fen {}", fen.join(" "))))?,
+        _ => {
+            return Err(UciParseError::new(
+                "position expects startpos or fen".to_string(),
+            ))
+        }
+    };
+    // Check each move's legality on the position it is played from
+    let mut current_board = board;
+    let mut chess_moves = Vec::<ChessMove>::with_capacity(moves.len());
+    for mv in moves {
+        let chess_move = ChessMove::from_str(mv)
+            .map_err(|_| UciParseError::new(format!("invalid move {mv}")))?;
+        if !current_board.legal(chess_move) {
+            return Err(UciParseError::new(format!("illegal move {mv}")));
+        }
+        current_board = current_board.make_move_new(chess_move);
+        chess_moves.push(chess_move);
+    }
+    Ok(UciCommand::Position {
+        board,
+        moves: chess_moves,
+    })
+}
+
+fn parse_go(tokens: Vec<&str>) -> Result<GoParams, UciParseError> {
+    let mut params = GoParams::default();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        let field = match token {
+            "wtime" => &mut params.wtime,
+            "btime" => &mut params.btime,
+            "winc" => &mut params.winc,
+            "binc" => &mut params.binc,
+            "movestogo" => &mut params.movestogo,
+            "movetime" => &mut params.movetime,
+            "infinite" => {
+                params.infinite = true;
+                continue;
+            }
+            // Unsupported limits (depth, nodes, ponder, ...) are ignored
+            _ => continue,
+        };
+        let value = tokens
+            .next()
+            .ok_or_else(|| UciParseError::new(format!("missing value for {token}")))?;
+        // Some GUIs send negative times once the clock ran out
+        let value = value
+            .parse::<i64>()
+            .map_err(|_| UciParseError::new(format!("invalid value {value} for {token}")))?;
+        *field = Some(value.max(0) as u64);
+    }
+    Ok(params)
+}
+
+pub fn format_info(info: &SearchInfo) -> String {
+    let time_ms = (info.elapsed * 1000_f32) as u64;
+    let nps = if info.elapsed > 0_f32 {
+        (info.iterations as f32 / info.elapsed) as u64
+    } else {
+        0
+    };
+    // Proven positions score as mates, others map the win ratio onto a centipawn-like scale
+    let score = match info.mate {
+        Some(mate) => format!("mate {mate}"),
+        None => {
+            let win_ratio = info.win_ratio.clamp(0.001, 0.999);
+            let score_cp = (400_f32 * (win_ratio / (1_f32 - win_ratio)).log10()) as i32;
+            format!("cp {score_cp}")
+        }
+    };
+    let principal_variation = info
+        .principal_variation
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        info.principal_variation.len(),
+        score,
+        info.iterations,
+        nps,
+        time_ms,
+        principal_variation
+    )
+}
+
+enum SearchRequest {
+    NewGame,
+    Threads(usize),
+    Position { board: Board, moves: Vec<ChessMove> },
+    Go(GoParams),
+    Quit,
+}
+
+/// Owns the search tree on its own thread so that `stop` can be read while searching.
+struct SearchState {
+    tree: Option<Tree>,
+    start_board: Board,
+    // Moves already applied to the tree, starting from `start_board`
+    moves: Vec<ChessMove>,
+    stop_flag: Arc<AtomicBool>,
+    config: SearchConfig,
+}
+
+impl SearchState {
+    fn new(stop_flag: Arc<AtomicBool>, config: SearchConfig) -> SearchState {
+        SearchState {
+            tree: None,
+            start_board: Board::default(),
+            moves: Vec::new(),
+            stop_flag,
+            config,
+        }
+    }
+
+    fn new_tree(&self, board: Board) -> Tree {
+        let mut tree = Tree::new(board, self.config.max_search_depth);
+        self.config.apply(&mut tree);
+        tree.set_stop_flag(self.stop_flag.clone());
+        tree.set_info_callback(Box::new(|info| println!("{}", format_info(info))));
+        tree
+    }
+
+    fn set_threads(&mut self, n_threads: usize) {
+        self.config.n_threads = n_threads;
+        if let Some(tree) = self.tree.as_mut() {
+            tree.set_n_threads(n_threads);
+        }
+    }
+
+    fn set_position(&mut self, board: Board, moves: Vec<ChessMove>) {
+        let is_continuation = board == self.start_board && moves.starts_with(&self.moves);
+        match self.tree.as_mut() {
+            // Reuse the tree by playing the new moves
+            Some(tree) if is_continuation => {
+                for chess_move in &moves[self.moves.len()..] {
+                    tree.provide_opponent_move(*chess_move);
+                }
+            }
+            // Rebuild the tree from the requested position
+            _ => {
+                let current_board = moves
+                    .iter()
+                    .fold(board, |board, chess_move| board.make_move_new(*chess_move));
+                self.tree = Some(self.new_tree(current_board));
+            }
+        }
+        self.start_board = board;
+        self.moves = moves;
+    }
+
+    fn go(&mut self, params: GoParams) {
+        if self.tree.is_none() {
+            self.tree = Some(self.new_tree(self.start_board));
+        }
+        let tree = self.tree.as_mut().unwrap();
+        let board = tree.head_board();
+        let search_time = params
+            .search_time(board.side_to_move(), &self.config.time_manager)
+            .unwrap_or(self.config.max_search_time);
+        tree.set_max_search_time(search_time);
+        // The tree advances its head to the chosen move
+        let Some(best_move) = tree.yield_best_move(board.side_to_move()) else {
+            self.wait_for_stop(&params);
+            println!("bestmove 0000");
+            return;
+        };
+        self.moves.push(best_move);
+        self.wait_for_stop(&params);
+        println!("bestmove {best_move}");
+    }
+
+    /// Holds back the `bestmove` of an infinite search until `stop`, even if the search ended
+    /// on its own (e.g. on a proven mate), as the protocol requires.
+    fn wait_for_stop(&self, params: &GoParams) {
+        while params.infinite && !self.stop_flag.load(Ordering::Relaxed) {
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+}
+
+fn search_loop(
+    receiver: Receiver<SearchRequest>,
+    stop_flag: Arc<AtomicBool>,
+    config: SearchConfig,
+) {
+    let mut state = SearchState::new(stop_flag, config);
+    while let Ok(request) = receiver.recv() {
+        match request {
+            // Options set by the GUI are kept
+            SearchRequest::NewGame => {
+                state = SearchState::new(state.stop_flag.clone(), state.config.clone())
+            }
+            SearchRequest::Threads(n_threads) => state.set_threads(n_threads),
+            SearchRequest::Position { board, moves } => state.set_position(board, moves),
+            SearchRequest::Go(params) => state.go(params),
+            SearchRequest::Quit => break,
+        }
+    }
+}
+
+/// Speaks the Universal Chess Interface over stdin/stdout until `quit` or end of input.
+///
+/// Fails before reading any command if the search configuration is invalid.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let config = get_search_config()?;
+    let default_threads = config.n_threads;
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let (request_sender, request_receiver) = mpsc::channel();
+    let search_stop_flag = stop_flag.clone();
+    let search_thread =
+        thread::spawn(move || search_loop(request_receiver, search_stop_flag, config));
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        debug!("UCI command received: {line}");
+        let request = match parse_command(&line) {
+            Ok(UciCommand::Uci) => {
+                println!("id name {ENGINE_NAME}");
+                println!("id author {ENGINE_AUTHOR}");
+                println!(
+                    "option name Threads type spin default {default_threads} min 1 max {MAX_THREADS}"
+                );
+                println!("uciok");
+                None
+            }
+            Ok(UciCommand::IsReady) => {
+                println!("readyok");
+                None
+            }
+            Ok(UciCommand::UciNewGame) => Some(SearchRequest::NewGame),
+            Ok(UciCommand::SetOption { name, value }) if name.eq_ignore_ascii_case("Threads") => {
+                match value.as_deref().map(usize::from_str) {
+                    Some(Ok(n_threads)) if (1..=MAX_THREADS).contains(&n_threads) => {
+                        Some(SearchRequest::Threads(n_threads))
+                    }
+                    _ => {
+                        println!("info string invalid Threads value");
+                        None
+                    }
+                }
+            }
+            // Unsupported options are ignored
+            Ok(UciCommand::SetOption { .. }) => None,
+            Ok(UciCommand::Position { board, moves }) => {
+                Some(SearchRequest::Position { board, moves })
+            }
+            Ok(UciCommand::Go(params)) => {
+                stop_flag.store(false, Ordering::Relaxed);
+                Some(SearchRequest::Go(params))
+            }
+            Ok(UciCommand::Stop) => {
+                stop_flag.store(true, Ordering::Relaxed);
+                None
+            }
+            Ok(UciCommand::Quit) => break,
+            // Unknown commands are ignored, as required by the protocol
+            Ok(UciCommand::Unknown(_)) => None,
+            Err(error) => {
+                println!("info string {error}");
+                None
+            }
+        };
+        if let Some(request) = request {
+            if request_sender.send(request).is_err() {
+                break;
+            }
+        }
+    }
+    // Interrupt a running search and wait for the search thread to finish
+    stop_flag.store(true, Ordering::Relaxed);
+    let _ = request_sender.send(SearchRequest::Quit);
+    let _ = search_thread.join();
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct UciParseError {
+    message: String,
+}
+
+impl UciParseError {
+    fn new(message: String) -> UciParseError {
+        UciParseError { message }
+    }
+}
+
+impl fmt::Display for UciParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to parse UCI command: {}", self.message)
+    }
+}
+
+impl std::error::Error for UciParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_go_line(line: &str) -> GoParams {
+        match parse_command(line) {
+            Ok(UciCommand::Go(params)) => params,
+            command => panic!("{line} parsed as {command:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_simple_commands() {
+        assert_eq!(parse_command("uci").unwrap(), UciCommand::Uci);
+        assert_eq!(parse_command("isready").unwrap(), UciCommand::IsReady);
+        assert_eq!(parse_command("ucinewgame").unwrap(), UciCommand::UciNewGame);
+        assert_eq!(parse_command("  stop ").unwrap(), UciCommand::Stop);
+        assert_eq!(parse_command("quit").unwrap(), UciCommand::Quit);
+        assert_eq!(
+            parse_command("ponderhit").unwrap(),
+            UciCommand::Unknown("ponderhit".to_string())
+        );
+        assert_eq!(
+            parse_command("").unwrap(),
+            UciCommand::Unknown(String::new())
+        );
+    }
+
+    #[test]
+    fn parses_setoption() {
+        assert_eq!(
+            parse_command("setoption name Threads value 4").unwrap(),
+            UciCommand::SetOption {
+                name: "Threads".to_string(),
+                value: Some("4".to_string())
+            }
+        );
+        assert_eq!(
+            parse_command("setoption name Clear Hash").unwrap(),
+            UciCommand::SetOption {
+                name: "Clear Hash".to_string(),
+                value: None
+            }
+        );
+        assert!(parse_command("setoption value 4").is_err());
+    }
+
+    #[test]
+    fn parses_position_with_moves() {
+        let e4 = ChessMove::from_str("e2e4").unwrap();
+        let e5 = ChessMove::from_str("e7e5").unwrap();
+        assert_eq!(
+            parse_command("position startpos moves e2e4 e7e5").unwrap(),
+            UciCommand::Position {
+                board: Board::default(),
+                moves: vec![e4, e5]
+            }
+        );
+        assert_eq!(
+            parse_command("position startpos").unwrap(),
+            UciCommand::Position {
+                board: Board::default(),
+                moves: vec![]
+            }
+        );
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+        assert_eq!(
+            parse_command(&format!("position fen {fen} moves a1a8")).unwrap(),
+            UciCommand::Position {
+                board: Board::from_str(fen).unwrap(),
+                moves: vec![ChessMove::from_str("a1a8").unwrap()]
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_positions() {
+        // Legal from the start position, but not once e2e4 was played
+        assert!(parse_command("position startpos moves e2e4 e2e4").is_err());
+        assert!(parse_command("position startpos moves e2e9").is_err());
+        assert!(parse_command("position fen 8/8/8 w - - 0 1").is_err());
+        assert!(parse_command("position fen 8/8/8/8/8/8/8/R5K1 w - - 0 1").is_err());
+        assert!(parse_command("position").is_err());
+        assert!(parse_command("position moves e2e4").is_err());
+    }
+
+    #[test]
+    fn parses_go_limits() {
+        assert_eq!(
+            parse_go_line("go wtime 60000 btime 50000 winc 1000 binc 2000 movestogo 20"),
+            GoParams {
+                wtime: Some(60000),
+                btime: Some(50000),
+                winc: Some(1000),
+                binc: Some(2000),
+                movestogo: Some(20),
+                ..GoParams::default()
+            }
+        );
+        assert_eq!(
+            parse_go_line("go movetime 1500"),
+            GoParams {
+                movetime: Some(1500),
+                ..GoParams::default()
+            }
+        );
+        assert!(parse_go_line("go infinite").infinite);
+        assert_eq!(parse_go_line("go"), GoParams::default());
+        // Unsupported limits are skipped, negative times count as a flagged clock
+        assert_eq!(
+            parse_go_line("go depth 5 wtime -100"),
+            GoParams {
+                wtime: Some(0),
+                ..GoParams::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_go() {
+        assert!(parse_command("go wtime").is_err());
+        assert!(parse_command("go movetime soon").is_err());
+    }
+
+    #[test]
+    fn go_limits_set_the_search_time() {
+        let time_manager = TimeManager::default();
+        assert_eq!(
+            parse_go_line("go movetime 1500").search_time(Color::White, &time_manager),
+            Some(1.5)
+        );
+        assert_eq!(
+            parse_go_line("go infinite").search_time(Color::White, &time_manager),
+            Some(f32::INFINITY)
+        );
+        // No clock for Black
+        assert_eq!(
+            parse_go_line("go wtime 60000").search_time(Color::Black, &time_manager),
+            None
+        );
+        let search_time = parse_go_line("go wtime 60000 btime 60000 movestogo 10")
+            .search_time(Color::White, &time_manager)
+            .unwrap();
+        assert!(search_time > 0_f32 && search_time <= 6_f32);
+    }
+
+    #[test]
+    fn proven_positions_score_as_mates() {
+        let search_info = |fen: &str, n_iterations: usize| {
+            let board = Board::from_str(fen).unwrap();
+            let mut tree = Tree::new(board, SearchConfig::default().max_search_depth);
+            tree.set_max_search_time(f32::MAX);
+            tree.set_max_iterations(Some(n_iterations));
+            tree.set_early_stop(false);
+            tree.yield_best_move(board.side_to_move());
+            tree.last_search_info().unwrap().clone()
+        };
+        // Mate in one for white, and a single black move into a mate in one
+        let info = format_info(&search_info("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 20_000));
+        assert!(info.contains(" score mate 1 "), "{info}");
+        let info = format_info(&search_info("7k/8/6K1/8/8/8/8/R7 b - - 0 1", 20_000));
+        assert!(info.contains(" score mate -1 "), "{info}");
+        // Unproven positions keep the centipawn score
+        let info = format_info(&search_info(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            500,
+        ));
+        assert!(info.contains(" score cp "), "{info}");
+    }
+
+    #[test]
+    fn infinite_search_waits_for_stop() {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let mut state = SearchState::new(stop_flag.clone(), SearchConfig::default());
+        // Proven at once, which ends the search on its own
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        state.set_position(board, Vec::new());
+        let go_params = parse_go_line("go infinite");
+        let search = thread::spawn(move || state.go(go_params));
+        thread::sleep(Duration::from_millis(500));
+        assert!(!search.is_finished(), "bestmove sent before stop");
+        stop_flag.store(true, Ordering::Relaxed);
+        search.join().unwrap();
+    }
+}
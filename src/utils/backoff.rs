@@ -0,0 +1,107 @@
+use rand::Rng;
+use std::time::Duration;
+
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Exponential backoff with jitter between reconnection attempts.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    // Consecutive failed attempts
+    attempt: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff::new(INITIAL_DELAY, MAX_DELAY)
+    }
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Backoff {
+        Backoff {
+            initial_delay,
+            max_delay,
+            attempt: 0,
+        }
+    }
+
+    /// Delay before the next attempt, doubling with each call up to the maximal delay.
+    ///
+    /// A random jitter of up to half the delay keeps concurrent reconnections apart.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2_u32.saturating_pow(self.attempt))
+            .min(self.max_delay);
+        self.attempt = self.attempt.saturating_add(1);
+        delay.mul_f32(rand::thread_rng().gen_range(0.5_f32, 1_f32))
+    }
+
+    pub async fn wait(&mut self) {
+        tokio::time::sleep(self.next_delay()).await;
+    }
+
+    /// Starts over from the initial delay, once a connection succeeded.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INITIAL: Duration = Duration::from_millis(100);
+    const MAX: Duration = Duration::from_millis(1000);
+
+    /// Checks the jittered `delay` lies within half of `expected` and `expected`.
+    fn assert_jittered(delay: Duration, expected: Duration) {
+        assert!(
+            expected / 2 <= delay && delay <= expected,
+            "{delay:?} outside the jitter of {expected:?}"
+        );
+    }
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        // Repeated to cover the jitter
+        for _ in 0..50 {
+            let mut backoff = Backoff::new(INITIAL, MAX);
+            let delays: Vec<Duration> = (0..8).map(|_| backoff.next_delay()).collect();
+            let expected_millis = [100, 200, 400, 800, 1000, 1000, 1000, 1000];
+            for (delay, expected) in delays.iter().zip(expected_millis) {
+                assert_jittered(*delay, Duration::from_millis(expected));
+            }
+            // Delays two attempts apart grow despite the jitter, until capped
+            assert!(delays[0] < delays[2] && delays[1] < delays[3]);
+            assert_eq!(backoff.attempt(), 8);
+        }
+    }
+
+    #[test]
+    fn attempts_never_overflow() {
+        let mut backoff = Backoff::new(INITIAL, MAX);
+        backoff.attempt = u32::MAX;
+        assert_jittered(backoff.next_delay(), MAX);
+        assert_eq!(backoff.attempt(), u32::MAX);
+    }
+
+    #[test]
+    fn reset_starts_over_from_the_initial_delay() {
+        let mut backoff = Backoff::new(INITIAL, MAX);
+        for _ in 0..5 {
+            backoff.next_delay();
+        }
+        backoff.reset();
+        assert_eq!(backoff.attempt(), 0);
+        assert_jittered(backoff.next_delay(), INITIAL);
+        assert_jittered(backoff.next_delay(), 2 * INITIAL);
+    }
+}
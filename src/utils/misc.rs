@@ -1,5 +1,6 @@
-use chess::Color as ChessColor;
+use chess::{Board, BoardBuilder, Color as ChessColor, Piece};
 use lichess_api::model::Color as ApiColor;
+use std::str::FromStr;
 
 pub fn api_to_chess_color(api_color: ApiColor) -> ChessColor {
     match api_color {
@@ -8,3 +9,19 @@ pub fn api_to_chess_color(api_color: ApiColor) -> ChessColor {
         _ => panic!("ApiColor Random received, how to handle??"),
     }
 }
+
+/// Reads the position of a fen, `None` if it is malformed.
+pub fn board_from_fen(fen: &str) -> Option<Board> {
+    let builder = BoardBuilder::from_str(fen).ok()?;
+    // `chess` panics on positions without exactly one king per side instead of rejecting them
+    let n_kings = |color| {
+        chess::ALL_SQUARES
+            .iter()
+            .filter(|x| builder[**x] == Some((Piece::King, color)))
+            .count()
+    };
+    if n_kings(ChessColor::White) != 1 || n_kings(ChessColor::Black) != 1 {
+        return None;
+    }
+    Board::try_from(builder).ok()
+}
@@ -1,5 +1,5 @@
+pub mod backoff;
 pub mod graph_visualization;
 pub mod misc;
 pub mod parse_args;
-pub mod rc_wrapper;
 pub mod threadpool;
@@ -1,12 +1,12 @@
+use crate::config::BotConfig;
+use crate::mcts::config::SearchConfig;
+use log::warn;
 use project_root::get_project_root;
-use rand::{seq::SliceRandom, thread_rng};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufReader};
-use std::path::{Path, PathBuf};
-use std::{env, fs, iter};
+use std::{env, fs};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiInfo {
@@ -14,32 +14,180 @@ pub struct ApiInfo {
     pub token: String,
 }
 
-fn get_file_path(path_elements: Vec<&str>) -> String {
-    let mut path = get_project_root().unwrap();
+/// Path of a file of the project, `None` when not run from within the project.
+fn get_file_path(path_elements: Vec<&str>) -> Option<String> {
+    let mut path = get_project_root().ok()?;
     path.extend(path_elements.iter());
-    path.to_string_lossy().to_string()
+    Some(path.to_string_lossy().to_string())
 }
 
 pub fn get_api_tokens() -> Result<ApiInfo, Box<dyn Error>> {
-    let api_config_path = get_file_path(vec!["configs", "api.json"]);
+    let api_config_path = get_file_path(vec!["configs", "api.json"])
+        .ok_or("Unable to find the project root holding configs/api.json")?;
 
     // Read the contents of the file
-    let file = File::open(api_config_path.clone())
-        .expect(&format!("Unable to open file at path {api_config_path}"));
+    let file = File::open(&api_config_path)
+        .map_err(|x| format!("Unable to open file at path {api_config_path}: {x}"))?;
 
     // Parse the JSON into a Config struct
     let api_config: ApiInfo = serde_json::from_reader(&file)?;
     Ok(api_config)
 }
 
-pub fn get_lorem_ipsum() -> String {
-    let path = get_file_path(vec!["assets", "lorem_ipsum.txt"]);
-    // Read the contents of the file
-    let mut content =
-        fs::read_to_string(path.clone()).expect(&format!("Unable to open file at path {}", path));
-    let snippets = content.split(".").collect::<Vec<&str>>();
-    snippets
-        .choose(&mut rand::thread_rng())
-        .unwrap()
-        .to_string()
+/// Loads the search configuration, from the path given by `--search-config <path>`, the
+/// `BROCCIU_SEARCH_CONFIG` environment variable or `configs/default_search.json` (in that order).
+/// Without any of them, e.g. when run outside of the project, the default configuration is used.
+///
+/// Single parameters are overridden by comma-separated `key=value` pairs in the `BROCCIU_SEARCH`
+/// environment variable and by `--search key=value` arguments, the latter taking precedence.
+pub fn get_search_config() -> Result<SearchConfig, Box<dyn Error>> {
+    let (json, overrides) = read_config("search")?;
+    let search_config = SearchConfig::from_json(&json, &overrides)?;
+    Ok(search_config)
+}
+
+/// Loads the bot configuration like the search configuration, from `--bot-config <path>`,
+/// `BROCCIU_BOT_CONFIG` or `configs/default_bot.json`, overridden by `BROCCIU_BOT` and `--bot`.
+/// Falls back to the default configuration likewise.
+pub fn get_bot_config() -> Result<BotConfig, Box<dyn Error>> {
+    let (json, overrides) = read_config("bot")?;
+    let bot_config = BotConfig::from_json(&json, &overrides)?;
+    Ok(bot_config)
+}
+
+/// Reads the JSON document of the `name` configuration and its `key=value` overrides.
+///
+/// A path given explicitly must be readable, while a missing default file yields an empty document.
+fn read_config(name: &str) -> Result<(String, Vec<String>), Box<dyn Error>> {
+    let args = env::args().collect::<Vec<String>>();
+    let env_name = format!("BROCCIU_{}", name.to_uppercase());
+    let config_path = arg_values(&args, &format!("--{name}-config"))
+        .pop()
+        .or_else(|| env::var(format!("{env_name}_CONFIG")).ok());
+    let json = match config_path {
+        Some(config_path) => fs::read_to_string(&config_path)
+            .map_err(|x| format!("Unable to read {name} config at path {config_path}: {x}"))?,
+        None => {
+            let default_path = get_file_path(vec!["configs", &format!("default_{name}.json")]);
+            match default_path.map(|x| (fs::read_to_string(&x), x)) {
+                Some((Ok(json), _)) => json,
+                Some((Err(x), default_path)) => {
+                    warn!(
+                        "Unable to read {name} config at path {default_path} ({x}), using defaults"
+                    );
+                    String::from("{}")
+                }
+                None => {
+                    warn!("No project root to read the default {name} config from, using defaults");
+                    String::from("{}")
+                }
+            }
+        }
+    };
+    let overrides = collect_overrides(name, env::var(env_name).ok(), &args);
+    Ok((json, overrides))
+}
+
+/// Value following each occurrence of the argument `name`.
+fn arg_values(args: &[String], name: &str) -> Vec<String> {
+    args.windows(2)
+        .filter(|x| x[0] == name)
+        .map(|x| x[1].clone())
+        .collect()
+}
+
+/// `key=value` overrides of the `name` configuration: the comma-separated ones of the
+/// environment variable, then the `--name` arguments. Later overrides take precedence.
+fn collect_overrides(name: &str, env_value: Option<String>, args: &[String]) -> Vec<String> {
+    let mut overrides = env_value
+        .map(|x| {
+            x.split(',')
+                .filter(|x| !x.is_empty())
+                .map(String::from)
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    overrides.extend(arg_values(args, &format!("--{name}")));
+    overrides
+}
+
+/// Sets the value of a `key=value` entry, the value is read as JSON and as a string otherwise.
+pub fn apply_override(value: &mut Value, entry: &str) -> Result<(), String> {
+    let (key, raw_value) = entry
+        .split_once('=')
+        .ok_or_else(|| format!("override {entry} is not key=value"))?;
+    let new_value =
+        serde_json::from_str(raw_value).unwrap_or_else(|_| Value::String(raw_value.to_string()));
+    // Wrap the value into one object per nested key
+    let patch = key.split('.').rev().fold(new_value, |x, key| {
+        Value::Object(Map::from_iter([(key.to_string(), x)]))
+    });
+    merge(value, patch);
+    Ok(())
+}
+
+/// Recursively copies the fields of `patch` into `value`.
+pub fn merge(value: &mut Value, patch: Value) {
+    match (value, patch) {
+        (Value::Object(object), Value::Object(patch)) => {
+            for (key, patch_value) in patch {
+                merge(object.entry(key).or_insert(Value::Null), patch_value);
+            }
+        }
+        (value, patch) => *value = patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn command_line_overrides_come_after_the_environment() {
+        let args = args(&[
+            "brocciu",
+            "--search",
+            "n_threads=4",
+            "--bot",
+            "max_concurrent_games=3",
+            "--search",
+            "rave.enabled=true",
+        ]);
+        let env_value = Some("n_threads=2,,early_stop=false".to_string());
+        assert_eq!(
+            collect_overrides("search", env_value, &args),
+            vec![
+                "n_threads=2",
+                "early_stop=false",
+                "n_threads=4",
+                "rave.enabled=true"
+            ]
+        );
+        assert_eq!(
+            collect_overrides("bot", None, &args),
+            vec!["max_concurrent_games=3"]
+        );
+    }
+
+    #[test]
+    fn overrides_set_nested_json_or_string_values() {
+        let mut value = json!({"rave": {"enabled": false, "schedule": {"Equivalence": 1000.0}}});
+        apply_override(&mut value, "rave.enabled=true").unwrap();
+        apply_override(&mut value, "selection_policy=AlphaZero").unwrap();
+        apply_override(&mut value, "n_threads=4").unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "rave": {"enabled": true, "schedule": {"Equivalence": 1000.0}},
+                "selection_policy": "AlphaZero",
+                "n_threads": 4
+            })
+        );
+        assert!(apply_override(&mut value, "n_threads").is_err());
+    }
 }
@@ -1,26 +1,27 @@
-use crate::game::BotGame;
-use log::debug;
+use log::{debug, warn};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use tokio::sync::oneshot;
 
-type JobFunc = dyn Send + 'static + FnMut() -> String;
+// Runs a scheduled function and sends its result
+type JobFunc = dyn Send + 'static + FnOnce();
 
 pub struct ThreadPool {
     workers: Vec<Worker>,
-    task_sender: mpsc::Sender<Job>,
+    // Dropped first when the pool is dropped, which ends the workers' loops
+    task_sender: Option<mpsc::Sender<Job>>,
 }
 
 struct Worker {
     id: usize,
-    thread: thread::JoinHandle<()>,
-    task_receiver: Arc<Mutex<Receiver<Job>>>,
+    thread: Option<thread::JoinHandle<()>>,
 }
 
 pub struct Job {
     function: Box<JobFunc>,
-    results_sender: Arc<Mutex<Sender<String>>>,
 }
 // type Job = Box<dyn Send + 'static + FnMut() -> String>;
 
@@ -41,21 +42,26 @@ impl ThreadPool {
         }
         let tp = ThreadPool {
             workers,
-            task_sender,
+            task_sender: Some(task_sender),
         };
         debug!("Threadpool insantiated");
         tp
     }
 
-    pub fn schedule_job<F>(&self, f: F) -> Receiver<String>
+    /// Runs `f` on the pool, the result can be awaited without blocking the async runtime.
+    pub fn schedule_job<F, R>(&self, f: F) -> oneshot::Receiver<R>
     where
-        F: Send + 'static + FnMut() -> String,
+        F: Send + 'static + FnOnce() -> R,
+        R: Send + 'static,
     {
-        let (results_sender, results_receiver) = mpsc::channel();
-        let results_sender = Arc::new(Mutex::new(results_sender));
+        let (results_sender, results_receiver) = oneshot::channel();
 
-        let job = Job::new(Box::new(f), results_sender.clone());
-        self.task_sender.send(job).unwrap();
+        let job = Job::new(f, results_sender);
+        self.task_sender
+            .as_ref()
+            .expect("Jobs are only scheduled before the pool is dropped")
+            .send(job)
+            .unwrap();
         debug!("Job scheduled");
         results_receiver
     }
@@ -63,30 +69,51 @@ impl ThreadPool {
 
 impl Worker {
     pub fn new(id: usize, task_receiver: Arc<Mutex<Receiver<Job>>>) -> Worker {
-        let t_receiver = task_receiver.clone();
         let thread = thread::spawn(move || loop {
-            let mut job = task_receiver.lock().unwrap().recv().unwrap();
-            let result: String = (*job.function)();
-            debug!("Worker {id} executed job; sending result ...");
-            let _ = job.results_sender.lock().unwrap().send(result);
+            // The pool dropped its sender, no job follows
+            let Ok(job) = task_receiver.lock().unwrap().recv() else {
+                debug!("Worker {id} stopped");
+                break;
+            };
+            // A panicking job drops its sender, which the receiver reports as an error
+            if panic::catch_unwind(AssertUnwindSafe(job.function)).is_err() {
+                warn!("Worker {id} job panicked");
+                continue;
+            }
+            debug!("Worker {id} executed job");
         });
         Worker {
             id,
-            thread,
-            task_receiver: t_receiver,
+            thread: Some(thread),
         }
     }
 }
 
+impl Drop for ThreadPool {
+    /// Lets the workers finish their current job and joins them.
+    fn drop(&mut self) {
+        drop(self.task_sender.take());
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                if thread.join().is_err() {
+                    warn!("Worker {} could not be joined", worker.id);
+                }
+            }
+        }
+        debug!("Threadpool shut down");
+    }
+}
+
 impl Job {
-    fn new<F>(f: F, results_sender: Arc<Mutex<Sender<String>>>) -> Job
+    fn new<F, R>(f: F, results_sender: oneshot::Sender<R>) -> Job
     where
-        F: Send + 'static + FnMut() -> String,
+        F: Send + 'static + FnOnce() -> R,
+        R: Send + 'static,
     {
-        let function = Box::new(f);
-        Job {
-            function,
-            results_sender,
-        }
+        let function = Box::new(move || {
+            // The receiver is gone if the game was dropped meanwhile
+            let _ = results_sender.send(f());
+        });
+        Job { function }
     }
 }
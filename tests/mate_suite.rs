@@ -0,0 +1,155 @@
+//! Mate-finding regression suite: within a fixed iteration budget, the tree has to play a move
+//! keeping the quickest forced mate and to avoid moves allowing a mate in one. Mates proven by
+//! the solver end the search early.
+use brocciu::mcts::outcome::GameOutcome;
+use brocciu::mcts::rave::RaveSchedule;
+use brocciu::mcts::search::MAX_SEARCH_DEPTH;
+use brocciu::mcts::tree::{SearchInfo, Tree};
+use chess::{Board, ChessMove, MoveGen};
+use std::str::FromStr;
+
+// Iteration budgets, deeper mates need more playouts to stand out
+const SHALLOW_ITERATIONS: usize = 20_000;
+const DEEP_ITERATIONS: usize = 100_000;
+
+const MATES_IN_ONE: [&str; 5] = [
+    "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+    "r5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1",
+    "k7/8/1K6/8/8/8/7Q/8 w - - 0 1",
+    "1k6/ppp5/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+];
+const MATES_IN_TWO: [&str; 4] = [
+    "r1b2k1r/ppp1bppp/8/1B1Q4/5q2/2P5/PPP2PPP/R3R1K1 w - - 1 1",
+    "2k5/8/8/8/8/8/1R6/R5K1 w - - 0 1",
+    "8/8/8/8/8/8/k7/1R2R1K1 w - - 0 1",
+    "6k1/8/8/8/8/8/8/RR4K1 w - - 0 1",
+];
+const MATES_IN_THREE: [&str; 2] = [
+    "2k5/8/8/8/8/8/8/R5KR w - - 0 1",
+    "8/5k2/8/8/8/8/8/RR4K1 w - - 0 1",
+];
+// Positions in which some moves allow a mate in one
+const AVOID_MATE: [&str; 2] = [
+    "6k1/5ppp/8/8/8/8/5PPP/R5K1 b - - 0 1",
+    "1k6/ppp5/8/8/8/8/5PPP/3R2K1 b - - 0 1",
+];
+
+/// Move played on `board` and the statistics of its search.
+fn search(
+    board: Board,
+    n_iterations: usize,
+    rave: Option<RaveSchedule>,
+) -> (ChessMove, SearchInfo) {
+    let mut tree = Tree::new(board, MAX_SEARCH_DEPTH);
+    // The iteration budget ends the search
+    tree.set_max_search_time(f32::MAX);
+    tree.set_max_iterations(Some(n_iterations));
+    tree.set_rave(rave);
+    let chess_move = tree
+        .yield_best_move(board.side_to_move())
+        .expect("The position has a legal move");
+    (chess_move, tree.last_search_info().unwrap().clone())
+}
+
+/// Whether the side to move on `board` mates in at most `n_moves` moves, whatever the defence.
+fn forces_mate(board: &Board, n_moves: usize) -> bool {
+    MoveGen::new_legal(board).any(|x| gets_mated(&board.make_move_new(x), n_moves))
+}
+
+/// Whether the side to move on `board` is mated within `n_moves` moves of the opponent.
+fn gets_mated(board: &Board, n_moves: usize) -> bool {
+    match GameOutcome::from_board(board) {
+        Some(outcome) => outcome == GameOutcome::won_by(!board.side_to_move()),
+        None => {
+            n_moves > 1
+                && MoveGen::new_legal(board)
+                    .all(|x| forces_mate(&board.make_move_new(x), n_moves - 1))
+        }
+    }
+}
+
+/// Checks the tree plays a move keeping a mate in `n_moves` in each of the `positions`.
+fn assert_finds_mates(
+    positions: &[&str],
+    n_moves: usize,
+    n_iterations: usize,
+    rave: Option<RaveSchedule>,
+) {
+    for fen in positions {
+        let board = Board::from_str(fen).expect("Invalid suite fen");
+        assert!(forces_mate(&board, n_moves), "No mate in {fen}");
+        let (chess_move, _) = search(board, n_iterations, rave);
+        assert!(
+            gets_mated(&board.make_move_new(chess_move), n_moves),
+            "{chess_move} misses the mate in {fen}"
+        );
+    }
+}
+
+/// Whether the opponent mates in one after `chess_move`.
+fn allows_mate_in_one(board: &Board, chess_move: ChessMove) -> bool {
+    let board = board.make_move_new(chess_move);
+    MoveGen::new_legal(&board).any(|x| {
+        GameOutcome::from_board(&board.make_move_new(x))
+            == Some(GameOutcome::won_by(board.side_to_move()))
+    })
+}
+
+#[test]
+fn finds_mates_in_one() {
+    assert_finds_mates(&MATES_IN_ONE, 1, SHALLOW_ITERATIONS, None);
+}
+
+#[test]
+fn finds_mates_in_two() {
+    assert_finds_mates(&MATES_IN_TWO, 2, SHALLOW_ITERATIONS, None);
+}
+
+#[test]
+fn finds_mates_in_three() {
+    assert_finds_mates(&MATES_IN_THREE, 3, DEEP_ITERATIONS, None);
+}
+
+#[test]
+fn finds_mates_with_rave() {
+    let rave = Some(RaveSchedule::default());
+    assert_finds_mates(&MATES_IN_ONE, 1, SHALLOW_ITERATIONS, rave);
+    assert_finds_mates(&MATES_IN_TWO, 2, SHALLOW_ITERATIONS, rave);
+}
+
+#[test]
+fn avoids_mates_in_one() {
+    for fen in AVOID_MATE {
+        let board = Board::from_str(fen).expect("Invalid suite fen");
+        let (chess_move, _) = search(board, SHALLOW_ITERATIONS, None);
+        assert!(
+            !allows_mate_in_one(&board, chess_move),
+            "{chess_move} allows a mate in {fen}"
+        );
+    }
+}
+
+#[test]
+fn plays_proven_mates_immediately() {
+    for fen in MATES_IN_ONE {
+        let board = Board::from_str(fen).expect("Invalid suite fen");
+        let (_, info) = search(board, SHALLOW_ITERATIONS, None);
+        assert!(
+            info.iterations < SHALLOW_ITERATIONS,
+            "Searched on after proving the mate in {fen}"
+        );
+        assert_eq!(info.win_ratio, 1_f32);
+    }
+}
+
+#[test]
+fn outcomes_credit_the_mating_side() {
+    let mated_white = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/r5K1 w - - 0 1").unwrap();
+    assert_eq!(
+        GameOutcome::from_board(&mated_white),
+        Some(GameOutcome::BlackWins)
+    );
+    assert_eq!(GameOutcome::BlackWins.score(chess::Color::Black), 1_f32);
+    assert_eq!(GameOutcome::from_board(&Board::default()), None);
+}